@@ -0,0 +1,163 @@
+use serde::Deserialize;
+
+/// Months and seasons of the campaign's calendar, used to tell the season from `--date`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Calendar {
+    /// Month names in order.
+    pub months: Vec<String>,
+    pub seasons: Vec<Season>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Season {
+    pub name: String,
+    /// Months that fall in the season.
+    pub months: Vec<String>,
+}
+
+impl Default for Calendar {
+    /// The Gregorian months with meteorological seasons of the northern hemisphere.
+    fn default() -> Self {
+        let names = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let season = |name: &str, months: &[&str]| Season {
+            name: name.to_string(),
+            months: names(months),
+        };
+        Calendar {
+            months: names(&[
+                "January",
+                "February",
+                "March",
+                "April",
+                "May",
+                "June",
+                "July",
+                "August",
+                "September",
+                "October",
+                "November",
+                "December",
+            ]),
+            seasons: vec![
+                season("Spring", &["March", "April", "May"]),
+                season("Summer", &["June", "July", "August"]),
+                season("Autumn", &["September", "October", "November"]),
+                season("Winter", &["December", "January", "February"]),
+            ],
+        }
+    }
+}
+
+impl Calendar {
+    /// Finds a season by name, ignoring case.
+    pub fn season(&self, name: &str) -> Option<&Season> {
+        self.seasons
+            .iter()
+            .find(|season| season.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Month named in `date`, such as `14 Mirtul 1492`, or given by number in an ISO date such
+    /// as `1492-05-14`.
+    pub fn month(&self, date: &str) -> Option<&str> {
+        let words: Vec<&str> = date
+            .split(|c: char| !c.is_alphanumeric() && c != '\'')
+            .filter(|word| !word.is_empty())
+            .collect();
+        let named = words.iter().find_map(|word| {
+            self.months
+                .iter()
+                .find(|month| month.eq_ignore_ascii_case(word))
+        });
+        if let Some(month) = named {
+            return Some(month);
+        }
+        match words.as_slice() {
+            [year, month, _] if year.len() >= 3 => {
+                let month: usize = month.parse().ok()?;
+                self.months.get(month.checked_sub(1)?).map(String::as_str)
+            }
+            _ => None,
+        }
+    }
+
+    /// Season `date` falls in, `None` when it names no month of the calendar.
+    pub fn season_of(&self, date: &str) -> Option<&Season> {
+        let month = self.month(date)?;
+        self.seasons
+            .iter()
+            .find(|season| season.months.iter().any(|m| m.eq_ignore_ascii_case(month)))
+    }
+
+    /// Problems with the months and seasons, if any.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (i, month) in self.months.iter().enumerate() {
+            if self.months[..i]
+                .iter()
+                .any(|other| other.eq_ignore_ascii_case(month))
+            {
+                problems.push(format!("month {month:?} is declared more than once"));
+            }
+        }
+        for (i, season) in self.seasons.iter().enumerate() {
+            if self.seasons[..i]
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&season.name))
+            {
+                problems.push(format!(
+                    "season {:?} is declared more than once",
+                    season.name
+                ));
+            }
+            for month in season.months.iter() {
+                if !self.months.iter().any(|m| m.eq_ignore_ascii_case(month)) {
+                    problems.push(format!(
+                        "season {:?} has month {month:?}, which is not in months",
+                        season.name
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn harptos() -> Calendar {
+        let months = ["Hammer", "Alturiak", "Ches", "Tarsakh", "Mirtul", "Kythorn"];
+        Calendar {
+            months: months.iter().map(|month| month.to_string()).collect(),
+            seasons: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn finds_named_month() {
+        let calendar = harptos();
+        assert_eq!(calendar.month("14 Mirtul 1492"), Some("Mirtul"));
+        assert_eq!(calendar.month("ches 3, 1492 DR"), Some("Ches"));
+        assert_eq!(calendar.month("Midwinter 1492"), None);
+    }
+
+    #[test]
+    fn finds_month_of_iso_date() {
+        let calendar = harptos();
+        assert_eq!(calendar.month("1492-05-14"), Some("Mirtul"));
+        assert_eq!(calendar.month("1492-00-14"), None);
+        assert_eq!(calendar.month("1492-13-14"), None);
+        assert_eq!(calendar.month("14-05-92"), None);
+    }
+
+    #[test]
+    fn finds_season_of_date() {
+        let calendar = Calendar::default();
+        let season = |date| calendar.season_of(date).map(|season| season.name.as_str());
+        assert_eq!(season("2024-01-15"), Some("Winter"));
+        assert_eq!(season("3 july 2024"), Some("Summer"));
+        assert_eq!(season("Yule"), None);
+    }
+}
@@ -0,0 +1,320 @@
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Generate the stock of a herb market from a config file.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path to the market config.
+    #[arg(short, long, global = true, default_value = "herb-market.config.toml")]
+    pub config: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub generate: GenerateArgs,
+}
+
+impl Cli {
+    /// Parses the command line of the process, exiting with the usage on errors.
+    pub fn parse_args() -> Self {
+        Cli::try_parse_args_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parses `args` like [`Parser::try_parse_from`], but rejects the options of the default
+    /// `generate` before a subcommand, which that subcommand would otherwise ignore. Only
+    /// `--config` is shared with every subcommand.
+    pub fn try_parse_args_from<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut command = Cli::command();
+        let matches = command.try_get_matches_from_mut(args)?;
+        if let Some((subcommand, _)) = matches.subcommand() {
+            let misplaced = command.get_arguments().find(|arg| {
+                let id = arg.get_id().as_str();
+                id != "config" && matches.value_source(id) == Some(ValueSource::CommandLine)
+            });
+            if let Some(arg) = misplaced {
+                let name = arg.get_long().unwrap_or(arg.get_id().as_str());
+                let message = format!(
+                    "--{name} must come after the subcommand, e.g. `{subcommand} --{name}`"
+                );
+                return Err(command.error(ErrorKind::ArgumentConflict, message));
+            }
+        }
+        Cli::from_arg_matches(&matches).map_err(|e| e.format(&mut command))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Generate the stock of a market. This is what runs when no command is given.
+    Generate(Box<GenerateArgs>),
+    /// Check the config for mistakes without generating a market.
+    Validate,
+    /// Add up amounts of money, e.g. `"3 gp 5 sp" 12sp`, and show the total in every
+    /// denomination of the config's currency.
+    Convert {
+        /// Amounts to add up. Bare numbers are in the currency's `price_unit`.
+        #[arg(required = true)]
+        amounts: Vec<String>,
+    },
+    /// Show a market saved with `generate --save`.
+    Show(ShowArgs),
+    /// Move a saved market forward in time, restocking herbs and letting prices drift, then
+    /// show it.
+    Advance {
+        #[command(flatten)]
+        show: ShowArgs,
+        /// Number of in-game days to move forward.
+        #[arg(long, default_value_t = 1)]
+        days: u32,
+        /// Season the market moves into, from the config's calendar.
+        #[arg(long)]
+        season: Option<String>,
+    },
+    /// Record a customer buying herbs from a saved market.
+    Buy(TradeArgs),
+    /// Record a customer selling herbs to a saved market.
+    Sell(TradeArgs),
+    /// Compare the availability of a herb across the towns in `[[settlements]]`.
+    Compare {
+        /// Name of the herb, ignoring case.
+        herb: String,
+        /// Seed for the markets, the same as given to `generate`. A random seed is used when
+        /// omitted.
+        #[arg(short, long)]
+        seed: Option<String>,
+        /// Output format of the comparison.
+        #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+        format: Format,
+    },
+    /// Gather herbs in the wild, searching the given biomes for some hours.
+    Forage(ForageArgs),
+    /// Report which `[[recipes]]` can be crafted from the stock of a saved or generated market,
+    /// what their ingredients cost and the cheapest place to buy them.
+    Craft(Box<CraftArgs>),
+    /// Print everything the config says about a herb, such as what it does.
+    Describe {
+        /// Name of the herb, ignoring case.
+        herb: String,
+        /// Output format of the description.
+        #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+        format: Format,
+    },
+    /// Print the purchases and sales recorded for a saved market as a receipt.
+    Ledger {
+        /// Path of the saved market.
+        state: PathBuf,
+        /// Only show trades with this customer.
+        #[arg(long)]
+        customer: Option<String>,
+        /// Output format of the receipt.
+        #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+        format: Format,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct TradeArgs {
+    /// Path of the saved market.
+    pub state: PathBuf,
+    /// Name of the herb, ignoring case.
+    pub herb: String,
+    /// Number of units traded.
+    #[arg(default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    pub quantity: u16,
+    /// Who is trading with the market, as recorded in the ledger.
+    #[arg(long, default_value = "Party")]
+    pub customer: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ForageArgs {
+    /// Biome the party searches, by name or alias. May be repeated.
+    #[arg(short, long = "biome", required = true)]
+    pub biomes: Vec<String>,
+    /// Hours spent searching. Each hour turns up one herb.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub hours: u32,
+    /// Result of the party's skill check, used for every hour.
+    #[arg(long, allow_hyphen_values = true)]
+    pub check: Option<i64>,
+    /// Modifier added to a check rolled each hour with the config's `[forage] check` dice.
+    #[arg(
+        short,
+        long,
+        default_value_t = 0,
+        allow_hyphen_values = true,
+        conflicts_with = "check"
+    )]
+    pub modifier: i64,
+    /// Season from the config's calendar. Herbs ignore their `seasons` when omitted.
+    #[arg(long)]
+    pub season: Option<String>,
+    /// Seed for the search. A random seed is used when omitted.
+    #[arg(short, long)]
+    pub seed: Option<String>,
+    /// Output format of the herbs found.
+    #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+    pub format: Format,
+    /// List every hour of the search along with its rolls.
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CraftArgs {
+    /// Saved market to craft from, instead of generating markets. Costs are what `buy` would
+    /// charge for each ingredient, including `buy_spread` and any rise in price from earlier ones.
+    #[arg(long, conflicts_with_all = [
+        "town", "all_towns", "biomes", "nearby_biomes", "merchants", "size", "rarity_shift",
+        "seed", "market", "date", "season", "event", "save",
+    ])]
+    pub state: Option<PathBuf>,
+    /// Only report the recipe with this name, ignoring case.
+    #[arg(long)]
+    pub recipe: Option<String>,
+    /// Markets to craft from, generated as with `generate`. With several, such as every shop
+    /// of a town, ingredients are bought wherever they are cheapest.
+    #[command(flatten)]
+    pub market: GenerateArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ShowArgs {
+    /// Path of the saved market.
+    pub state: PathBuf,
+    /// Output format of the stock.
+    #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+    pub format: Format,
+    /// Show the tags, weight, source and description of herbs, and any dice rolled, in table
+    /// output.
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct GenerateArgs {
+    /// Town from `[[settlements]]` in the config to generate the market of, by name.
+    #[arg(short, long)]
+    pub town: Option<String>,
+    /// Generate the market of every town in `[[settlements]]`.
+    #[arg(long, conflicts_with_all = ["town", "market", "save"])]
+    pub all_towns: bool,
+    /// Biome local to the market, by name or alias. May be repeated. Together with
+    /// `--nearby-biome`, replaces `local_biomes` and `biome_distances` from the config.
+    #[arg(short, long = "biome")]
+    pub biomes: Vec<String>,
+    /// Biome near the market, by name or alias. May be repeated. Together with `--biome`,
+    /// replaces `local_biomes` and `biome_distances` from the config.
+    #[arg(short, long = "nearby-biome")]
+    pub nearby_biomes: Vec<String>,
+    /// Merchant from `[[merchants]]` whose shop to generate. May be repeated, and replaces the
+    /// town's own `merchants`.
+    #[arg(long = "merchant")]
+    pub merchants: Vec<String>,
+    /// Size of the market from `[[sizes]]`, replaces `size` from the config or the town.
+    #[arg(long)]
+    pub size: Option<String>,
+    /// Rarity steps added to non-local herbs, replaces `not_local_rarity_increase` from the config.
+    #[arg(short, long)]
+    pub rarity_shift: Option<u8>,
+    /// Seed for the market, a number or any text. A random seed is used when omitted.
+    #[arg(short, long)]
+    pub seed: Option<String>,
+    /// Output format of the generated stock.
+    #[arg(short, long, value_enum, default_value_t = Format::Markdown)]
+    pub format: Format,
+    /// Show the tags, weight, source and description of herbs, and the dice rolled for prices
+    /// and quantities, in table output.
+    #[arg(short, long)]
+    pub verbose: bool,
+    /// Write the output to this file instead of stdout.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Append to the output file instead of replacing it, with CSV and TSV only. The header row
+    /// is skipped when the file already has content.
+    #[arg(short, long, requires = "output")]
+    pub append: bool,
+    /// Name of the market in JSON, CSV and TSV output. Defaults to the town, or the config file
+    /// name.
+    #[arg(short, long)]
+    pub market: Option<String>,
+    /// Date of the market in JSON, CSV and TSV output, in whatever form the campaign uses. When
+    /// it names a month of the config's calendar, herbs are stocked for that month's season.
+    #[arg(short, long)]
+    pub date: Option<String>,
+    /// Season of the market from the config's calendar, replaces the one told by `--date`.
+    #[arg(long)]
+    pub season: Option<String>,
+    /// Event from `[[events]]` happening at the market, instead of rolling one with
+    /// `event_chance`.
+    #[arg(long)]
+    pub event: Option<String>,
+    /// Save the generated market to this file, so it can be restocked with `advance` later.
+    #[arg(long)]
+    pub save: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum Format {
+    /// Table wrapped in a fenced code block, ready to paste into chat.
+    Markdown,
+    /// Bare table.
+    Table,
+    /// JSON document with the stock and the seed, config and biomes it was generated from.
+    Json,
+    /// Comma-separated values with one row per herb.
+    Csv,
+    /// Tab-separated values with one row per herb.
+    Tsv,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_before_subcommand() {
+        let cli = Cli::try_parse_args_from(["herb-market", "-c", "town.toml", "validate"]).unwrap();
+        assert_eq!(cli.config, PathBuf::from("town.toml"));
+        assert!(matches!(cli.command, Some(Command::Validate)));
+
+        let cli =
+            Cli::try_parse_args_from(["herb-market", "--config", "town.toml", "show", "m.json"])
+                .unwrap();
+        assert_eq!(cli.config, PathBuf::from("town.toml"));
+        assert!(matches!(cli.command, Some(Command::Show(_))));
+    }
+
+    #[test]
+    fn config_after_subcommand() {
+        let cli = Cli::try_parse_args_from(["herb-market", "validate", "-c", "town.toml"]).unwrap();
+        assert_eq!(cli.config, PathBuf::from("town.toml"));
+    }
+
+    #[test]
+    fn rejects_generate_options_before_subcommand() {
+        let error = Cli::try_parse_args_from(["herb-market", "-s", "5", "generate"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+        assert!(Cli::try_parse_args_from(["herb-market", "-t", "Saltmarsh", "craft"]).is_err());
+
+        let cli = Cli::try_parse_args_from(["herb-market", "generate", "-s", "5"]).unwrap();
+        let Some(Command::Generate(args)) = cli.command else {
+            panic!("expected generate, got {:?}", cli.command);
+        };
+        assert_eq!(args.seed.as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn generate_without_subcommand() {
+        let cli = Cli::try_parse_args_from(["herb-market", "-c", "town.toml", "-s", "42"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.generate.seed.as_deref(), Some("42"));
+    }
+}
@@ -0,0 +1,1664 @@
+use crate::calendar::Calendar;
+use crate::craft::Recipe;
+use crate::currency::Currency;
+use crate::dice::Dice;
+use crate::forage::ForageConfig;
+use crate::quantity::Quantity;
+use rand::Rng;
+use serde::de::value::SeqAccessDeserializer;
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs::read_to_string;
+use std::hash::Hash;
+use std::path::Path;
+
+/// Name of one of the rarity tiers declared in `[[rarities]]`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Rarity(String);
+
+impl Rarity {
+    /// The tier after this one in the configured order, or `None` for the last tier.
+    pub fn next_rarity(&self, rarities: &RarityConfigs) -> Option<Self> {
+        let i = rarities.position(self)?;
+        rarities.0.get(i + 1).map(|next| next.name.clone())
+    }
+}
+
+impl Display for Rarity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Name of one of the biomes declared in `[[biomes]]`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Biome(String);
+
+impl Display for Biome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BiomeConfig {
+    pub name: Biome,
+    /// Name shown in tables and spreadsheets, `name` when not set.
+    pub display_name: Option<String>,
+    /// Other names herbs, `local_biomes` and `--biome` may use for this biome.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+impl BiomeConfig {
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name.0)
+    }
+
+    fn names(&self) -> impl Iterator<Item = &str> {
+        [self.name.0.as_str()]
+            .into_iter()
+            .chain(self.display_name.as_deref())
+            .chain(self.aliases.iter().map(String::as_str))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct BiomeConfigs(Vec<BiomeConfig>);
+
+impl Default for BiomeConfigs {
+    /// The ten biomes built in before they could be declared, for configs written back then.
+    fn default() -> Self {
+        let names = [
+            "MostTerrain",
+            "Coastal",
+            "Underdark",
+            "Desert",
+            "Mountain",
+            "Swamp",
+            "Forest",
+            "Arctic",
+            "Hills",
+            "Grasslands",
+        ];
+        BiomeConfigs(
+            names
+                .into_iter()
+                .map(|name| BiomeConfig {
+                    name: Biome(name.to_string()),
+                    display_name: None,
+                    aliases: match name {
+                        "MostTerrain" => vec!["most-terrain".to_string()],
+                        _ => Vec::new(),
+                    },
+                })
+                .collect(),
+        )
+    }
+}
+
+impl BiomeConfigs {
+    /// Finds a biome by its name, display name or one of its aliases, ignoring case.
+    pub fn resolve(&self, name: &str) -> Option<&BiomeConfig> {
+        self.0
+            .iter()
+            .find(|config| config.names().any(|n| n.eq_ignore_ascii_case(name)))
+    }
+
+    pub fn config(&self, biome: &Biome) -> Option<&BiomeConfig> {
+        self.0.iter().find(|config| &config.name == biome)
+    }
+
+    pub fn display_name<'a>(&'a self, biome: &'a Biome) -> &'a str {
+        self.config(biome)
+            .map(BiomeConfig::display_name)
+            .unwrap_or(&biome.0)
+    }
+
+    fn canonicalize(&self, biome: &mut Biome) {
+        if let Some(config) = self.resolve(&biome.0) {
+            *biome = config.name.clone();
+        }
+    }
+
+    fn canonicalize_locality(
+        &self,
+        local_biomes: &mut HashSet<Biome>,
+        biome_distances: &mut HashMap<Biome, Distance>,
+    ) {
+        *local_biomes = local_biomes
+            .drain()
+            .map(|mut biome| {
+                self.canonicalize(&mut biome);
+                biome
+            })
+            .collect();
+        *biome_distances = biome_distances
+            .drain()
+            .map(|(mut biome, distance)| {
+                self.canonicalize(&mut biome);
+                (biome, distance)
+            })
+            .collect();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Herb {
+    pub name: String,
+    pub rarity: Rarity,
+    pub biomes: Vec<Biome>,
+    /// Replaces the price of the herb's tier.
+    #[serde(flatten)]
+    pub price: OptionalPrice,
+    /// Applied to every price rolled for the herb.
+    #[serde(default = "default_multiplier")]
+    pub price_multiplier: f32,
+    /// Replaces the likelihood of the herb's tier.
+    pub likelihood: Option<f32>,
+    pub max_quantity: Option<u16>,
+    #[serde(default)]
+    pub never_in_stock: bool,
+    /// Free-form kinds of the herb, e.g. `healing`, that merchants can pick their stock by.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Changes to the herb's chance and price in some seasons, by season name.
+    #[serde(default)]
+    pub seasons: HashMap<String, SeasonModifier>,
+    /// Whether the herb is only stocked in the seasons listed in `seasons`.
+    #[serde(default)]
+    pub seasonal: bool,
+    /// What the herb looks like, for the GM to read out.
+    pub description: Option<String>,
+    /// What the herb does when used.
+    pub effect: Option<String>,
+    /// Book the herb comes from.
+    pub source: Option<Source>,
+    /// Weight of one unit, in pounds.
+    pub weight: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub book: String,
+    pub page: Option<u32>,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.page {
+            Some(page) => write!(f, "{}, p. {page}", self.book),
+            None => f.write_str(&self.book),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SeasonModifier {
+    /// Multiplier for the chance the herb is in stock.
+    #[serde(default = "default_multiplier")]
+    pub likelihood_multiplier: f32,
+    /// Multiplier for the herb's price.
+    #[serde(default = "default_multiplier")]
+    pub price_multiplier: f32,
+}
+
+impl Herb {
+    /// How the herb's chance and price change in `season`, `None` when the herb can't be
+    /// stocked in it.
+    pub fn in_season(&self, season: &str) -> Option<SeasonModifier> {
+        let modifier = self
+            .seasons
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(season))
+            .map(|(_, modifier)| modifier.clone());
+        match modifier {
+            Some(modifier) => Some(modifier),
+            None if self.seasonal => None,
+            None => Some(SeasonModifier {
+                likelihood_multiplier: 1.0,
+                price_multiplier: 1.0,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RarityConfig {
+    pub name: Rarity,
+    #[serde(flatten)]
+    pub price: Price,
+    /// Chance a herb of this tier is in stock at all.
+    pub likelihood: f32,
+    #[serde(default)]
+    pub quantity: Quantity,
+    /// Average number of days between restocks of a herb of this tier in a saved market.
+    #[serde(default = "default_restock_days")]
+    pub restock_days: f32,
+    #[serde(default)]
+    pub demand: Demand,
+}
+
+fn default_restock_days() -> f32 {
+    7.0
+}
+
+/// How prices in a saved market react to trade and time.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Demand {
+    /// Fraction a herb's price rises by for each unit customers buy.
+    pub purchase_impact: f32,
+    /// Fraction a herb's price falls by for each unit customers sell to the market.
+    pub sale_impact: f32,
+    /// Fraction of the gap between a herb's price and its baseline closed each day.
+    pub reversion: f32,
+    /// Lowest a price may fall, as a multiple of the baseline.
+    pub min_multiplier: f32,
+    /// Highest a price may rise, as a multiple of the baseline.
+    pub max_multiplier: f32,
+}
+
+impl Default for Demand {
+    fn default() -> Self {
+        Demand {
+            purchase_impact: 0.03,
+            sale_impact: 0.03,
+            reversion: 0.2,
+            min_multiplier: 0.5,
+            max_multiplier: 2.0,
+        }
+    }
+}
+
+impl Demand {
+    /// `price` clamped between the multipliers of `baseline`.
+    pub fn clamp(&self, price: f64, baseline: u64) -> u64 {
+        let baseline = baseline as f64;
+        price
+            .clamp(
+                baseline * f64::from(self.min_multiplier),
+                baseline * f64::from(self.max_multiplier),
+            )
+            .round() as u64
+    }
+
+    /// Problems with the parameters, if any.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (name, value) in [
+            ("purchase_impact", self.purchase_impact),
+            ("sale_impact", self.sale_impact),
+            ("reversion", self.reversion),
+        ] {
+            if !(0.0..1.0).contains(&value) {
+                problems.push(format!(
+                    "demand.{name} is {value}, it must be at least 0 and less than 1"
+                ));
+            }
+        }
+        if !(self.min_multiplier.is_finite() && self.min_multiplier > 0.0) {
+            problems.push(format!(
+                "demand.min_multiplier is {}, it must be greater than 0",
+                self.min_multiplier
+            ));
+        }
+        if !(self.max_multiplier.is_finite() && self.max_multiplier >= self.min_multiplier) {
+            problems.push(format!(
+                "demand.max_multiplier is {}, it must be at least min_multiplier",
+                self.max_multiplier
+            ));
+        }
+        problems
+    }
+}
+
+/// Price of a herb, either as `price_lower` and `price_upper` or as a dice expression in
+/// `price`, counted in `price_unit` or the currency's `price_unit` when that isn't set.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "PriceFields")]
+pub enum Price {
+    /// Uniform between the two bounds, inclusive.
+    Range {
+        price_lower: u16,
+        price_upper: u16,
+        price_unit: Option<String>,
+    },
+    Dice {
+        price: Dice,
+        price_unit: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+struct PriceFields {
+    price_lower: Option<u16>,
+    price_upper: Option<u16>,
+    price: Option<Dice>,
+    price_unit: Option<String>,
+}
+
+impl TryFrom<PriceFields> for Price {
+    type Error = String;
+
+    fn try_from(fields: PriceFields) -> Result<Self, Self::Error> {
+        OptionalPrice::try_from(fields)?
+            .0
+            .ok_or_else(|| "expected either price, or both price_lower and price_upper".to_string())
+    }
+}
+
+/// A [`Price`] that may be left out entirely.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(try_from = "PriceFields")]
+pub struct OptionalPrice(pub Option<Price>);
+
+impl TryFrom<PriceFields> for OptionalPrice {
+    type Error = String;
+
+    fn try_from(fields: PriceFields) -> Result<Self, Self::Error> {
+        match fields {
+            PriceFields {
+                price_lower: Some(price_lower),
+                price_upper: Some(price_upper),
+                price: None,
+                price_unit,
+            } => Ok(OptionalPrice(Some(Price::Range {
+                price_lower,
+                price_upper,
+                price_unit,
+            }))),
+            PriceFields {
+                price_lower: None,
+                price_upper: None,
+                price: Some(price),
+                price_unit,
+            } => Ok(OptionalPrice(Some(Price::Dice { price, price_unit }))),
+            PriceFields {
+                price_lower: None,
+                price_upper: None,
+                price: None,
+                price_unit: None,
+            } => Ok(OptionalPrice(None)),
+            _ => Err("expected either price, or both price_lower and price_upper".to_string()),
+        }
+    }
+}
+
+impl Price {
+    fn price_unit(&self) -> Option<&str> {
+        match self {
+            Price::Range { price_unit, .. } | Price::Dice { price_unit, .. } => {
+                price_unit.as_deref()
+            }
+        }
+    }
+
+    /// Rolls a price in the smallest denomination of `currency`, along with the dice breakdown
+    /// when it came from a dice expression.
+    pub fn roll<R: Rng>(&self, currency: &Currency, rng: &mut R) -> (u64, Option<String>) {
+        let unit = currency.unit(self.price_unit());
+        let unit_value = unit.map_or(1, |unit| unit.value);
+        match self {
+            Price::Range {
+                price_lower,
+                price_upper,
+                ..
+            } => (
+                u64::from(rng.gen_range(*price_lower..=*price_upper)) * unit_value,
+                None,
+            ),
+            Price::Dice { price, .. } => {
+                let roll = price.roll(rng);
+                let unit_name = unit.map_or("", |unit| unit.name.as_str());
+                (
+                    roll.total.max(0) as u64 * unit_value,
+                    Some(format!("{} {unit_name}", roll.breakdown)),
+                )
+            }
+        }
+    }
+
+    /// Price halfway between the lowest and highest it can roll, in the smallest denomination of
+    /// `currency`.
+    pub fn average(&self, currency: &Currency) -> f64 {
+        let unit_value = currency
+            .unit(self.price_unit())
+            .map_or(1, |unit| unit.value);
+        let (lower, upper) = match self {
+            Price::Range {
+                price_lower,
+                price_upper,
+                ..
+            } => (i64::from(*price_lower), i64::from(*price_upper)),
+            Price::Dice { price, .. } => (price.min().max(0), price.max().max(0)),
+        };
+        (lower as f64 + upper as f64) / 2.0 * unit_value as f64
+    }
+
+    /// Problems with the bounds and unit, if any.
+    pub fn check(&self, currency: &Currency) -> Vec<String> {
+        let mut problems = Vec::new();
+        match self {
+            Price::Range {
+                price_lower,
+                price_upper,
+                ..
+            } if price_lower > price_upper => problems.push(format!(
+                "price_lower ({price_lower}) is greater than price_upper ({price_upper})"
+            )),
+            Price::Dice { price, .. } if price.min() < 0 => {
+                problems.push(format!("price {price} can roll below 0"))
+            }
+            _ => {}
+        }
+        let upper = match self {
+            Price::Range { price_upper, .. } => u64::from(*price_upper),
+            Price::Dice { price, .. } => price.max().max(0) as u64,
+        };
+        let unit_value = currency
+            .unit(self.price_unit())
+            .map_or(1, |unit| unit.value);
+        if upper.checked_mul(unit_value).is_none() {
+            problems.push(
+                "price can roll more than can be counted in the smallest denomination".to_string(),
+            );
+        }
+        if let Some(unit) = self.price_unit() {
+            if currency.denomination(unit).is_none() {
+                problems.push(format!("price_unit {unit:?} is not a denomination"));
+            }
+        }
+        problems
+    }
+}
+
+/// Rarity tiers from most to least common.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RarityConfigs(Vec<RarityConfig>);
+
+/// Keys of the `[rarities]` table configs used before tiers were declared in `[[rarities]]`,
+/// along with the tier each became.
+const LEGACY_RARITIES: [(&str, &str); 4] = [
+    ("common", "Common"),
+    ("uncommon", "Uncommon"),
+    ("rare", "Rare"),
+    ("very_rare", "VeryRare"),
+];
+
+impl<'de> Deserialize<'de> for RarityConfigs {
+    /// Reads `[[rarities]]`, or the old `[rarities]` table with its four fixed tiers.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RaritiesVisitor;
+
+        impl<'de> Visitor<'de> for RaritiesVisitor {
+            type Value = RarityConfigs;
+
+            fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+                f.write_str("an array of [[rarities]]")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Vec::deserialize(SeqAccessDeserializer::new(seq)).map(RarityConfigs)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut tiers = HashMap::new();
+                while let Some((key, value)) = map.next_entry::<String, toml::Value>()? {
+                    tiers.insert(key, value);
+                }
+                let mut configs = Vec::new();
+                for (key, name) in LEGACY_RARITIES {
+                    let Some(mut value) = tiers.remove(key) else {
+                        continue;
+                    };
+                    if let toml::Value::Table(table) = &mut value {
+                        table.insert("name".to_string(), toml::Value::from(name));
+                    }
+                    let config = RarityConfig::deserialize(value)
+                        .map_err(|e| de::Error::custom(format!("rarities.{key}: {e}")))?;
+                    configs.push(config);
+                }
+                if let Some(key) = tiers.keys().min() {
+                    return Err(de::Error::custom(format!(
+                        "unknown rarity {key:?} in [rarities], declare tiers with [[rarities]] \
+                         to add your own"
+                    )));
+                }
+                Ok(RarityConfigs(configs))
+            }
+        }
+
+        deserializer.deserialize_any(RaritiesVisitor)
+    }
+}
+
+impl RarityConfigs {
+    /// Settings of `rarity`, `None` if no tier has that name.
+    pub fn config(&self, rarity: &Rarity) -> Option<&RarityConfig> {
+        self.0.iter().find(|config| &config.name == rarity)
+    }
+
+    fn position(&self, rarity: &Rarity) -> Option<usize> {
+        self.0.iter().position(|config| &config.name == rarity)
+    }
+}
+
+/// What happens to a herb whose non-local rarity increase goes past the last tier.
+#[derive(Debug, Clone, PartialEq, Deserialize, Default)]
+#[serde(tag = "policy", rename_all = "lowercase")]
+pub enum RarityOverflow {
+    /// The herb is never stocked.
+    #[default]
+    Drop,
+    /// The herb is stocked at the last tier.
+    Cap,
+    /// The herb is stocked at an extra tier past the last one.
+    Exotic(ExoticConfig),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExoticConfig {
+    #[serde(default = "default_exotic_name")]
+    pub name: Rarity,
+    pub likelihood: f32,
+    /// Applied to prices rolled for the last tier.
+    pub price_multiplier: f32,
+}
+
+fn default_exotic_name() -> Rarity {
+    Rarity("Exotic".to_string())
+}
+
+impl ExoticConfig {
+    /// Settings of the exotic tier. Prices and quantities are those of the last tier, with
+    /// `price_multiplier` left for the caller to apply.
+    pub fn rarity_config(&self, last: &RarityConfig) -> RarityConfig {
+        RarityConfig {
+            name: self.name.clone(),
+            price: last.price.clone(),
+            likelihood: self.likelihood,
+            quantity: last.quantity.clone(),
+            restock_days: last.restock_days,
+            demand: last.demand.clone(),
+        }
+    }
+}
+
+/// How far a biome is from the market, in `biome_distances`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Distance {
+    Tier(DistanceTier),
+    /// Exact number of rarity steps added to herbs from the biome.
+    Steps(u8),
+    /// Brought in by trade, e.g. along one of `routes`.
+    Imported(Import),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Import {
+    /// Rarity steps added to herbs from the biome.
+    pub steps: u8,
+    /// Fraction added to prices of herbs from the biome for transport.
+    pub markup: f32,
+}
+
+/// A trade route between two settlements, bringing each the herbs local to the other.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Route {
+    pub from: String,
+    pub to: String,
+    /// Length of the route, in whatever unit `route_markup` is given for, e.g. days of travel.
+    pub length: f32,
+    /// Rarity steps added to herbs brought along the route. Defaults to
+    /// `nearby_rarity_increase` of the town they're brought to.
+    pub rarity_increase: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceTier {
+    /// Same as being listed in `local_biomes`.
+    Local,
+    /// Adds `nearby_rarity_increase` steps.
+    Nearby,
+    /// Adds `not_local_rarity_increase` steps, same as not being listed at all.
+    Distant,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub biomes: BiomeConfigs,
+    pub local_biomes: HashSet<Biome>,
+    /// Distances of biomes that are neither local nor as far as `not_local_rarity_increase`
+    /// implies.
+    #[serde(default)]
+    pub biome_distances: HashMap<Biome, Distance>,
+    #[serde(default = "default_nearby_rarity_increase")]
+    pub nearby_rarity_increase: u8,
+    pub not_local_rarity_increase: u8,
+    #[serde(default)]
+    pub currency: Currency,
+    pub rarities: RarityConfigs,
+    #[serde(default)]
+    pub rarity_overflow: RarityOverflow,
+    /// Most a price in a saved market may change in a day, as a fraction of it.
+    #[serde(default = "default_price_drift")]
+    pub price_drift: f32,
+    /// Fraction added to prices when customers buy from a saved market.
+    #[serde(default)]
+    pub buy_spread: f32,
+    /// Fraction taken off prices when customers sell to a saved market.
+    #[serde(default = "default_sell_spread")]
+    pub sell_spread: f32,
+    /// Towns sharing the herbs of this config, each with its own locality.
+    #[serde(default)]
+    pub settlements: Vec<Settlement>,
+    /// Trade routes between `settlements`.
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// Fraction added to prices of herbs brought along a route, per unit of its length.
+    #[serde(default = "default_route_markup")]
+    pub route_markup: f32,
+    /// Settlement sizes, from smallest to largest.
+    #[serde(default)]
+    pub sizes: Vec<SizeConfig>,
+    /// Size of the market, one of `sizes`. Stock isn't scaled when unset.
+    pub size: Option<String>,
+    #[serde(default)]
+    pub merchants: Vec<MerchantConfig>,
+    #[serde(default)]
+    pub calendar: Calendar,
+    #[serde(default)]
+    pub forage: ForageConfig,
+    /// Events that may happen at a market.
+    #[serde(default)]
+    pub events: Vec<EventConfig>,
+    /// Chance a generated market has one of `events`.
+    #[serde(default)]
+    pub event_chance: f32,
+    /// Event happening at the market, one of `events`. Rolled with `event_chance` when unset.
+    pub event: Option<String>,
+    /// Season of the market, one of `calendar.seasons`. Herbs ignore their `seasons` when
+    /// unset.
+    pub season: Option<String>,
+    pub herbs: Vec<Herb>,
+    /// What can be brewed from `herbs`.
+    #[serde(default)]
+    pub recipes: Vec<Recipe>,
+}
+
+/// A town in a world config. Herbs, rarities and currency are shared by every town.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Settlement {
+    pub name: String,
+    pub local_biomes: HashSet<Biome>,
+    #[serde(default)]
+    pub biome_distances: HashMap<Biome, Distance>,
+    /// Replaces `nearby_rarity_increase` of the config in this town.
+    pub nearby_rarity_increase: Option<u8>,
+    /// Replaces `not_local_rarity_increase` of the config in this town.
+    pub not_local_rarity_increase: Option<u8>,
+    /// Replaces `size` of the config in this town.
+    pub size: Option<String>,
+    /// Shops in this town, from `merchants`. Generating the town generates each of them.
+    #[serde(default)]
+    pub merchants: Vec<String>,
+}
+
+/// How the size of a settlement scales its stock, e.g. a village or a city.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SizeConfig {
+    pub name: String,
+    #[serde(flatten)]
+    pub scaling: StockScaling,
+}
+
+/// A kind of seller, e.g. a hedge witch or a black-market dealer, that only stocks some of the
+/// herbs and scales their stock.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MerchantConfig {
+    pub name: String,
+    /// Herbs the merchant sells.
+    #[serde(flatten)]
+    pub herbs: HerbFilter,
+    #[serde(flatten)]
+    pub scaling: StockScaling,
+}
+
+/// Picks herbs by biome and tag, for merchants and events.
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct HerbFilter {
+    /// Only herbs found in one of these biomes. All biomes when empty.
+    #[serde(default)]
+    pub biomes: Vec<Biome>,
+    /// Only herbs with one of these tags. All herbs when empty.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl HerbFilter {
+    pub fn matches(&self, herb: &Herb) -> bool {
+        (self.biomes.is_empty() || herb.biomes.iter().any(|biome| self.biomes.contains(biome)))
+            && (self.tags.is_empty()
+                || herb
+                    .tags
+                    .iter()
+                    .any(|tag| self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))))
+    }
+}
+
+/// Something happening at a market, e.g. a blight or a caravan glut, that changes the stock of
+/// some herbs.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct EventConfig {
+    pub name: String,
+    /// Shown above the stock for the GM to narrate.
+    #[serde(default)]
+    pub description: String,
+    /// Chance of this event relative to the other events.
+    #[serde(default = "default_multiplier")]
+    pub weight: f32,
+    /// Herbs affected by the event.
+    #[serde(flatten)]
+    pub herbs: HerbFilter,
+    /// Multiplier for the chance affected herbs are in stock.
+    #[serde(default = "default_multiplier")]
+    pub likelihood_multiplier: f32,
+    /// Multiplier for quantities of affected herbs.
+    #[serde(default = "default_multiplier")]
+    pub quantity_multiplier: f32,
+    /// Multiplier for prices of affected herbs.
+    #[serde(default = "default_multiplier")]
+    pub price_multiplier: f32,
+}
+
+/// Multipliers a settlement size or merchant applies to the stock.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StockScaling {
+    /// Multipliers for the chance herbs are in stock, by rarity tier. Tiers left out keep their
+    /// chance.
+    #[serde(default)]
+    pub likelihood: HashMap<Rarity, f32>,
+    /// Multiplier for quantities and `max_quantity` of herbs.
+    #[serde(default = "default_multiplier")]
+    pub quantity_multiplier: f32,
+    /// Multiplier for prices.
+    #[serde(default = "default_multiplier")]
+    pub price_multiplier: f32,
+}
+
+fn default_multiplier() -> f32 {
+    1.0
+}
+
+fn default_nearby_rarity_increase() -> u8 {
+    1
+}
+
+fn default_price_drift() -> f32 {
+    0.05
+}
+
+fn default_sell_spread() -> f32 {
+    0.5
+}
+
+fn default_route_markup() -> f32 {
+    0.01
+}
+
+impl Config {
+    /// Rarity steps added to herbs found in `biome`, and their transport markup.
+    pub fn biome_import(&self, biome: &Biome) -> Import {
+        let steps = |steps| Import { steps, markup: 0.0 };
+        if self.local_biomes.contains(biome) {
+            return steps(0);
+        }
+        match self.biome_distances.get(biome) {
+            Some(Distance::Tier(DistanceTier::Local)) => steps(0),
+            Some(Distance::Tier(DistanceTier::Nearby)) => steps(self.nearby_rarity_increase),
+            Some(Distance::Tier(DistanceTier::Distant)) | None => {
+                steps(self.not_local_rarity_increase)
+            }
+            Some(Distance::Steps(n)) => steps(*n),
+            Some(Distance::Imported(import)) => *import,
+        }
+    }
+
+    /// Whether any of `herb`'s biomes is local, either listed in `local_biomes` or at the
+    /// `local` distance.
+    pub fn is_local(&self, herb: &Herb) -> bool {
+        herb.biomes.iter().any(|biome| {
+            self.local_biomes.contains(biome)
+                || self.biome_distances.get(biome) == Some(&Distance::Tier(DistanceTier::Local))
+        })
+    }
+
+    /// Rarity steps added to `herb`, going by whichever of its biomes is closest.
+    pub fn rarity_increase(&self, herb: &Herb) -> u8 {
+        self.herb_import(herb).steps
+    }
+
+    /// Rarity steps added to `herb` and its transport markup, going by whichever of its biomes is closest
+    /// and, among those, cheapest to bring in.
+    pub fn herb_import(&self, herb: &Herb) -> Import {
+        herb.biomes
+            .iter()
+            .map(|biome| self.biome_import(biome))
+            .min_by(|a, b| a.steps.cmp(&b.steps).then(a.markup.total_cmp(&b.markup)))
+            .unwrap_or(Import {
+                steps: self.not_local_rarity_increase,
+                markup: 0.0,
+            })
+    }
+
+    /// Herb called `name`, ignoring case.
+    pub fn herb(&self, name: &str) -> Option<&Herb> {
+        self.herbs
+            .iter()
+            .find(|herb| herb.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Settlement called `name`, ignoring case.
+    pub fn settlement(&self, name: &str) -> Option<&Settlement> {
+        self.settlements
+            .iter()
+            .find(|settlement| settlement.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The config as seen from `settlement`, with its locality in place of the config's own.
+    /// Biomes local to towns at the other end of its routes are imported, unless they're
+    /// already closer.
+    pub fn at(&self, settlement: &Settlement) -> Config {
+        let mut cfg = self.clone();
+        cfg.local_biomes = settlement.local_biomes.clone();
+        cfg.biome_distances = settlement.biome_distances.clone();
+        if let Some(nearby_rarity_increase) = settlement.nearby_rarity_increase {
+            cfg.nearby_rarity_increase = nearby_rarity_increase;
+        }
+        if let Some(not_local_rarity_increase) = settlement.not_local_rarity_increase {
+            cfg.not_local_rarity_increase = not_local_rarity_increase;
+        }
+        if let Some(size) = &settlement.size {
+            cfg.size = Some(size.clone());
+        }
+        for route in self.routes.iter() {
+            let other = if route.from.eq_ignore_ascii_case(&settlement.name) {
+                &route.to
+            } else if route.to.eq_ignore_ascii_case(&settlement.name) {
+                &route.from
+            } else {
+                continue;
+            };
+            let Some(other) = self.settlement(other) else {
+                continue;
+            };
+            let import = Import {
+                steps: route.rarity_increase.unwrap_or(cfg.nearby_rarity_increase),
+                markup: self.route_markup * route.length,
+            };
+            let mut biomes: Vec<&Biome> = other.local_biomes.iter().collect();
+            biomes.sort();
+            for biome in biomes {
+                let current = cfg.biome_import(biome);
+                if (import.steps, import.markup) < (current.steps, current.markup) {
+                    cfg.biome_distances
+                        .insert(biome.clone(), Distance::Imported(import));
+                }
+            }
+        }
+        cfg
+    }
+
+    /// Settings of the market's `size`, `None` when it has none.
+    pub fn size_config(&self) -> Option<&SizeConfig> {
+        let size = self.size.as_deref()?;
+        self.sizes
+            .iter()
+            .find(|size_config| size_config.name.eq_ignore_ascii_case(size))
+    }
+
+    /// Settings of the market's `event`, `None` when it has none.
+    pub fn event_config(&self) -> Option<&EventConfig> {
+        let event = self.event.as_deref()?;
+        self.events
+            .iter()
+            .find(|event_config| event_config.name.eq_ignore_ascii_case(event))
+    }
+
+    /// Merchant called `name`, ignoring case.
+    pub fn merchant(&self, name: &str) -> Option<&MerchantConfig> {
+        self.merchants
+            .iter()
+            .find(|merchant| merchant.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Reads the config at `path` and checks it with [`Config::validate`].
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text =
+            read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        let cfg: Config =
+            toml::from_str(text.as_str()).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(cfg.resolved()?)
+    }
+
+    /// Finishes a freshly deserialized config by resolving biome aliases and validating it.
+    pub fn resolved(mut self) -> Result<Self, ValidationErrors> {
+        self.resolve_aliases();
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Replaces biome aliases and display names used by herbs, settlements, `local_biomes` and
+    /// `biome_distances` with the biome's name.
+    fn resolve_aliases(&mut self) {
+        for herb in self.herbs.iter_mut() {
+            for biome in herb.biomes.iter_mut() {
+                self.biomes.canonicalize(biome);
+            }
+        }
+        self.biomes
+            .canonicalize_locality(&mut self.local_biomes, &mut self.biome_distances);
+        let filters = self
+            .merchants
+            .iter_mut()
+            .map(|merchant| &mut merchant.herbs)
+            .chain(self.events.iter_mut().map(|event| &mut event.herbs));
+        for filter in filters {
+            for biome in filter.biomes.iter_mut() {
+                self.biomes.canonicalize(biome);
+            }
+        }
+        for settlement in self.settlements.iter_mut() {
+            self.biomes.canonicalize_locality(
+                &mut settlement.local_biomes,
+                &mut settlement.biome_distances,
+            );
+        }
+    }
+
+    /// Problems with the biomes of a locality, such as biomes that aren't declared, along with
+    /// the field they're in.
+    fn check_locality(
+        &self,
+        local_biomes: &HashSet<Biome>,
+        biome_distances: &HashMap<Biome, Distance>,
+    ) -> Vec<(&'static str, String)> {
+        let mut local_biomes: Vec<&Biome> = local_biomes.iter().collect();
+        local_biomes.sort();
+        let mut distant_biomes: Vec<&Biome> = biome_distances.keys().collect();
+        distant_biomes.sort();
+        local_biomes
+            .into_iter()
+            .map(|biome| ("local_biomes", biome))
+            .chain(
+                distant_biomes
+                    .iter()
+                    .copied()
+                    .map(|biome| ("[biome_distances]", biome)),
+            )
+            .filter(|(_, biome)| self.biomes.config(biome).is_none())
+            .map(|(field, biome)| {
+                let message = format!("biome {:?} is not declared in [[biomes]]", biome.0);
+                (field, message)
+            })
+            .chain(distant_biomes.iter().copied().filter_map(|biome| {
+                let Some(Distance::Imported(import)) = biome_distances.get(biome) else {
+                    return None;
+                };
+                if import.markup.is_finite() && import.markup >= 0.0 {
+                    return None;
+                }
+                let message = format!(
+                    "biome {:?} has markup {}, it must be at least 0",
+                    biome.0, import.markup
+                );
+                Some(("[biome_distances]", message))
+            }))
+            .collect()
+    }
+
+    /// Problem with a reference to one of `sizes`, if any.
+    fn check_size(&self, size: Option<&str>) -> Option<String> {
+        let size = size?;
+        match self
+            .sizes
+            .iter()
+            .any(|config| config.name.eq_ignore_ascii_case(size))
+        {
+            true => None,
+            false => Some(format!("size {size:?} is not declared in [[sizes]]")),
+        }
+    }
+
+    /// Problems with the multipliers of a size or merchant, if any.
+    fn check_scaling(&self, scaling: &StockScaling) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut likelihood: Vec<(&Rarity, &f32)> = scaling.likelihood.iter().collect();
+        likelihood.sort_by_key(|(rarity, _)| &rarity.0);
+        for (rarity, multiplier) in likelihood {
+            let exotic = match &self.rarity_overflow {
+                RarityOverflow::Exotic(exotic) => &exotic.name == rarity,
+                _ => false,
+            };
+            if self.rarities.config(rarity).is_none() && !exotic {
+                problems.push(format!(
+                    "rarity {:?} is not declared in [[rarities]]",
+                    rarity.0
+                ));
+            }
+            if !(multiplier.is_finite() && *multiplier >= 0.0) {
+                problems.push(format!(
+                    "likelihood multiplier of {:?} is {multiplier}, it must be at least 0",
+                    rarity.0
+                ));
+            }
+        }
+        for (name, multiplier) in [
+            ("quantity_multiplier", scaling.quantity_multiplier),
+            ("price_multiplier", scaling.price_multiplier),
+        ] {
+            if !(multiplier.is_finite() && multiplier > 0.0) {
+                problems.push(format!("{name} is {multiplier}, it must be greater than 0"));
+            }
+        }
+        problems
+    }
+
+    /// Checks everything deserialization alone can't, collecting all problems instead of
+    /// stopping at the first.
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors(Vec::new());
+        errors.push("[currency]", self.currency.check());
+        errors.push("[calendar]", self.calendar.check());
+        errors.push("[forage]", self.forage.check(&self.rarities));
+        if let Some(season) = &self.season {
+            if self.calendar.season(season).is_none() {
+                errors.push(
+                    "season",
+                    [format!("season {season:?} is not declared in [calendar]")],
+                );
+            }
+        }
+        if self.rarities.0.is_empty() {
+            errors.push("[[rarities]]", ["no rarity tiers are declared".to_string()]);
+        }
+        let mut rarity_names = HashMap::new();
+        for (i, rarity_config) in self.rarities.0.iter().enumerate() {
+            let location = format!("[[rarities]] #{} ({:?})", i + 1, rarity_config.name.0);
+            let name = rarity_config.name.clone();
+            errors.push(
+                &location,
+                check_unique(&mut rarity_names, name, i, "[[rarities]]"),
+            );
+            errors.push(&location, self.check_rarity(rarity_config));
+        }
+        errors.push(
+            "price_drift",
+            check_fraction("price_drift", self.price_drift),
+        );
+        errors.push(
+            "buy_spread",
+            check_at_least_zero("buy_spread", self.buy_spread),
+        );
+        errors.push(
+            "sell_spread",
+            check_fraction("sell_spread", self.sell_spread),
+        );
+        if let RarityOverflow::Exotic(exotic) = &self.rarity_overflow {
+            errors.push("[rarity_overflow]", self.check_exotic(exotic));
+        }
+        let mut biome_names = HashMap::new();
+        for (i, biome_config) in self.biomes.0.iter().enumerate() {
+            let location = format!("[[biomes]] #{} ({:?})", i + 1, biome_config.name.0);
+            let mut own_names = HashSet::new();
+            for name in biome_config.names() {
+                if !own_names.insert(name.to_ascii_lowercase()) {
+                    continue;
+                }
+                if let Some(first) = biome_names.insert(name.to_ascii_lowercase(), i) {
+                    errors.push(
+                        &location,
+                        [format!(
+                            "{name:?} is already used as a name or alias by [[biomes]] #{}",
+                            first + 1
+                        )],
+                    );
+                }
+            }
+        }
+        for (field, message) in self.check_locality(&self.local_biomes, &self.biome_distances) {
+            errors.push(field, [message]);
+        }
+        let mut settlement_names = HashMap::new();
+        for (i, settlement) in self.settlements.iter().enumerate() {
+            let location = format!("[[settlements]] #{} ({:?})", i + 1, settlement.name);
+            errors.push(&location, check_name(&settlement.name));
+            let name = settlement.name.to_lowercase();
+            errors.push(
+                &location,
+                check_unique(&mut settlement_names, name, i, "[[settlements]]"),
+            );
+            errors.push(&location, self.check_settlement(settlement));
+        }
+        for (i, route) in self.routes.iter().enumerate() {
+            let location = format!("[[routes]] #{} ({:?} to {:?})", i + 1, route.from, route.to);
+            errors.push(&location, self.check_route(route));
+        }
+        errors.push(
+            "route_markup",
+            check_at_least_zero("route_markup", self.route_markup),
+        );
+        errors.push("size", self.check_size(self.size.as_deref()));
+        let mut size_names = HashMap::new();
+        for (i, size) in self.sizes.iter().enumerate() {
+            let location = format!("[[sizes]] #{} ({:?})", i + 1, size.name);
+            let name = size.name.to_lowercase();
+            errors.push(
+                &location,
+                check_unique(&mut size_names, name, i, "[[sizes]]"),
+            );
+            errors.push(&location, self.check_scaling(&size.scaling));
+        }
+        let mut merchant_names = HashMap::new();
+        for (i, merchant) in self.merchants.iter().enumerate() {
+            let location = format!("[[merchants]] #{} ({:?})", i + 1, merchant.name);
+            let name = merchant.name.to_lowercase();
+            errors.push(
+                &location,
+                check_unique(&mut merchant_names, name, i, "[[merchants]]"),
+            );
+            errors.push(&location, self.check_biomes(&merchant.herbs.biomes));
+            errors.push(&location, self.check_scaling(&merchant.scaling));
+        }
+        errors.push("event_chance", self.check_event_chance());
+        if let Some(event) = &self.event {
+            if self.event_config().is_none() {
+                errors.push(
+                    "event",
+                    [format!("event {event:?} is not declared in [[events]]")],
+                );
+            }
+        }
+        let mut event_names = HashMap::new();
+        for (i, event) in self.events.iter().enumerate() {
+            let location = format!("[[events]] #{} ({:?})", i + 1, event.name);
+            let name = event.name.to_lowercase();
+            errors.push(
+                &location,
+                check_unique(&mut event_names, name, i, "[[events]]"),
+            );
+            errors.push(&location, self.check_event(event));
+        }
+        let mut herb_names = HashMap::new();
+        for (i, herb) in self.herbs.iter().enumerate() {
+            let location = format!("[[herbs]] #{} ({:?})", i + 1, herb.name);
+            errors.push(&location, check_name(&herb.name));
+            let name = herb.name.to_lowercase();
+            errors.push(
+                &location,
+                check_unique(&mut herb_names, name, i, "[[herbs]]"),
+            );
+            errors.push(&location, self.check_herb(herb));
+        }
+        let mut recipe_names = HashMap::new();
+        for (i, recipe) in self.recipes.iter().enumerate() {
+            let location = format!("[[recipes]] #{} ({:?})", i + 1, recipe.name);
+            let name = recipe.name.to_lowercase();
+            errors.push(
+                &location,
+                check_unique(&mut recipe_names, name, i, "[[recipes]]"),
+            );
+            errors.push(&location, self.check_recipe(recipe));
+        }
+        match errors.0.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// Problems with the settings of a rarity tier, if any.
+    fn check_rarity(&self, rarity_config: &RarityConfig) -> Vec<String> {
+        let mut problems: Vec<String> = check_fraction("likelihood", rarity_config.likelihood)
+            .into_iter()
+            .collect();
+        if !(rarity_config.restock_days.is_finite() && rarity_config.restock_days >= 1.0) {
+            problems.push(format!(
+                "restock_days is {}, it must be at least 1",
+                rarity_config.restock_days
+            ));
+        }
+        problems.extend(rarity_config.demand.check());
+        problems.extend(rarity_config.quantity.check());
+        problems.extend(rarity_config.price.check(&self.currency));
+        problems
+    }
+
+    /// Problems with the extra tier of `rarity_overflow`, if any.
+    fn check_exotic(&self, exotic: &ExoticConfig) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.rarities.config(&exotic.name).is_some() {
+            problems.push(format!(
+                "name {:?} is already used by [[rarities]]",
+                exotic.name.0
+            ));
+        }
+        problems.extend(check_fraction("likelihood", exotic.likelihood));
+        problems.extend(check_positive("price_multiplier", exotic.price_multiplier));
+        problems
+    }
+
+    /// Problems with a settlement, if any.
+    fn check_settlement(&self, settlement: &Settlement) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (field, message) in
+            self.check_locality(&settlement.local_biomes, &settlement.biome_distances)
+        {
+            problems.push(format!("{field}: {message}"));
+        }
+        problems.extend(self.check_size(settlement.size.as_deref()));
+        for merchant in settlement.merchants.iter() {
+            if self.merchant(merchant).is_none() {
+                problems.push(format!(
+                    "merchant {merchant:?} is not declared in [[merchants]]"
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Problems with a trade route, if any.
+    fn check_route(&self, route: &Route) -> Vec<String> {
+        let mut problems = Vec::new();
+        for town in [&route.from, &route.to] {
+            if self.settlement(town).is_none() {
+                problems.push(format!(
+                    "settlement {town:?} is not declared in [[settlements]]"
+                ));
+            }
+        }
+        if route.from.eq_ignore_ascii_case(&route.to) {
+            problems.push("from and to are the same settlement".to_string());
+        }
+        problems.extend(check_at_least_zero("length", route.length));
+        problems
+    }
+
+    /// Problems with `event_chance`, if any.
+    fn check_event_chance(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(0.0..=1.0).contains(&self.event_chance) {
+            problems.push(format!(
+                "event_chance is {}, it must be between 0 and 1",
+                self.event_chance
+            ));
+        }
+        if self.event_chance > 0.0 && self.events.iter().all(|event| event.weight <= 0.0) {
+            problems.push("event_chance is set but no event has a weight above 0".to_string());
+        }
+        problems
+    }
+
+    /// Problems with an event, if any.
+    fn check_event(&self, event: &EventConfig) -> Vec<String> {
+        let mut problems = self.check_biomes(&event.herbs.biomes);
+        for (name, value) in [
+            ("weight", event.weight),
+            ("likelihood_multiplier", event.likelihood_multiplier),
+            ("quantity_multiplier", event.quantity_multiplier),
+            ("price_multiplier", event.price_multiplier),
+        ] {
+            problems.extend(check_at_least_zero(name, value));
+        }
+        problems
+    }
+
+    /// Problems with a herb, if any.
+    fn check_herb(&self, herb: &Herb) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.rarities.config(&herb.rarity).is_none() {
+            problems.push(format!(
+                "rarity {:?} is not declared in [[rarities]]",
+                herb.rarity.0
+            ));
+        }
+        problems.extend(self.check_biomes(&herb.biomes));
+        if let Some(price) = &herb.price.0 {
+            problems.extend(price.check(&self.currency));
+        }
+        problems.extend(check_positive("price_multiplier", herb.price_multiplier));
+        if let Some(likelihood) = herb.likelihood {
+            problems.extend(check_fraction("likelihood", likelihood));
+        }
+        let mut seasons: Vec<(&String, &SeasonModifier)> = herb.seasons.iter().collect();
+        seasons.sort_by_key(|(name, _)| *name);
+        for (name, modifier) in seasons {
+            if self.calendar.season(name).is_none() {
+                problems.push(format!("season {name:?} is not declared in [calendar]"));
+            }
+            for (field, multiplier) in [
+                ("likelihood_multiplier", modifier.likelihood_multiplier),
+                ("price_multiplier", modifier.price_multiplier),
+            ] {
+                problems.extend(check_at_least_zero(
+                    &format!("{field} of season {name:?}"),
+                    multiplier,
+                ));
+            }
+        }
+        if herb.seasonal && herb.seasons.is_empty() {
+            problems.push("seasonal is set but no seasons are listed".to_string());
+        }
+        if let Some(weight) = herb.weight {
+            problems.extend(check_at_least_zero("weight", weight));
+        }
+        if herb.max_quantity == Some(0) {
+            problems.push("max_quantity is 0, use never_in_stock instead".to_string());
+        }
+        if herb.biomes.is_empty() {
+            problems.push("biomes is empty".to_string());
+        }
+        problems
+    }
+
+    /// Problems with a recipe, if any.
+    fn check_recipe(&self, recipe: &Recipe) -> Vec<String> {
+        let mut problems = Vec::new();
+        if recipe.ingredients.is_empty() {
+            problems.push("recipe has no ingredients".to_string());
+        }
+        for (i, ingredient) in recipe.ingredients.iter().enumerate() {
+            if self.herb(&ingredient.herb).is_none() {
+                problems.push(format!(
+                    "herb {:?} is not declared in [[herbs]]",
+                    ingredient.herb
+                ));
+            }
+            if recipe.ingredients[..i]
+                .iter()
+                .any(|other| other.herb.eq_ignore_ascii_case(&ingredient.herb))
+            {
+                problems.push(format!(
+                    "herb {:?} is listed more than once",
+                    ingredient.herb
+                ));
+            }
+            if ingredient.count == 0 {
+                problems.push(format!(
+                    "count of {:?} is 0, it must be at least 1",
+                    ingredient.herb
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Biomes of `biomes` that aren't declared, if any.
+    fn check_biomes(&self, biomes: &[Biome]) -> Vec<String> {
+        biomes
+            .iter()
+            .filter(|biome| self.biomes.config(biome).is_none())
+            .map(|biome| format!("biome {:?} is not declared in [[biomes]]", biome.0))
+            .collect()
+    }
+}
+
+/// Problem with `name` when it is empty.
+fn check_name(name: &str) -> Option<String> {
+    match name.trim().is_empty() {
+        true => Some("name is empty".to_string()),
+        false => None,
+    }
+}
+
+/// Problem with the `i`th entry of `section` when an earlier one already has its name `key`.
+fn check_unique<K: Hash + Eq>(
+    names: &mut HashMap<K, usize>,
+    key: K,
+    i: usize,
+    section: &str,
+) -> Option<String> {
+    let first = names.insert(key, i)?;
+    Some(format!("name is already used by {section} #{}", first + 1))
+}
+
+/// Problem with field `name` unless `value` is at least 0 and less than 1.
+fn check_fraction(name: &str, value: f32) -> Option<String> {
+    match (0.0..1.0).contains(&value) {
+        true => None,
+        false => Some(format!(
+            "{name} is {value}, it must be at least 0 and less than 1"
+        )),
+    }
+}
+
+/// Problem with field `name` unless `value` is a number of at least 0.
+fn check_at_least_zero(name: &str, value: f32) -> Option<String> {
+    match value.is_finite() && value >= 0.0 {
+        true => None,
+        false => Some(format!("{name} is {value}, it must be at least 0")),
+    }
+}
+
+/// Problem with field `name` unless `value` is a number greater than 0.
+fn check_positive(name: &str, value: f32) -> Option<String> {
+    match value.is_finite() && value > 0.0 {
+        true => None,
+        false => Some(format!("{name} is {value}, it must be greater than 0")),
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationError {
+    /// Where in the TOML the problem is, e.g. `[[rarities]] #3 ("Rare")`.
+    pub location: String,
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl ValidationErrors {
+    /// Records `problems` found at `location`.
+    fn push(&mut self, location: &str, problems: impl IntoIterator<Item = String>) {
+        self.0
+            .extend(problems.into_iter().map(|message| ValidationError {
+                location: location.to_string(),
+                message,
+            }));
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "config has {} problem(s):", self.0.len())?;
+        for error in self.0.iter() {
+            write!(f, "\n  {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationErrors {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Config {
+        toml::from_str::<Config>(text).unwrap().resolved().unwrap()
+    }
+
+    /// Problems `validate` reports with the config in `text`.
+    fn errors(text: &str) -> Vec<String> {
+        let error = toml::from_str::<Config>(text)
+            .unwrap()
+            .resolved()
+            .unwrap_err();
+        error.0.iter().map(ValidationError::to_string).collect()
+    }
+
+    #[test]
+    fn loads_legacy_rarities_and_builtin_biomes() {
+        let cfg = parse(
+            r#"
+            local_biomes = ["MostTerrain", "Coastal"]
+            not_local_rarity_increase = 2
+
+            [rarities]
+            common = { price_lower = 3, price_upper = 20, likelihood = 0.75 }
+            uncommon = { price_lower = 21, price_upper = 50, likelihood = 0.4 }
+            rare = { price_lower = 51, price_upper = 150, likelihood = 0.1 }
+            very_rare = { price_lower = 151, price_upper = 500, likelihood = 0.03 }
+
+            [[herbs]]
+            name = "Wisp Stalks"
+            rarity = "VeryRare"
+            biomes = ["Forest", "Underdark"]
+            "#,
+        );
+        let names: Vec<String> = cfg.rarities.0.iter().map(|r| r.name.0.clone()).collect();
+        assert_eq!(names, ["Common", "Uncommon", "Rare", "VeryRare"]);
+        assert_eq!(cfg.biomes.0.len(), 10);
+        assert_eq!(
+            cfg.biomes
+                .resolve("most-terrain")
+                .map(|b| b.name.0.as_str()),
+            Some("MostTerrain")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_legacy_rarity() {
+        let error = toml::from_str::<Config>(
+            r#"
+            local_biomes = []
+            not_local_rarity_increase = 2
+            herbs = []
+
+            [rarities]
+            legendary = { price_lower = 3, price_upper = 20, likelihood = 0.75 }
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("legendary"), "{error}");
+    }
+
+    #[test]
+    fn local_herbs_ignore_rarity_increase() {
+        let cfg = parse(
+            r#"
+            local_biomes = ["Forest"]
+            not_local_rarity_increase = 0
+
+            [biome_distances]
+            Desert = 0
+            Swamp = "local"
+
+            [[rarities]]
+            name = "Common"
+            price_lower = 3
+            price_upper = 20
+            likelihood = 0.75
+
+            [[herbs]]
+            name = "Arrow Root"
+            rarity = "Common"
+            biomes = ["Forest"]
+
+            [[herbs]]
+            name = "Dried Ephedra"
+            rarity = "Common"
+            biomes = ["Desert", "Mountain"]
+
+            [[herbs]]
+            name = "Mandrake Root"
+            rarity = "Common"
+            biomes = ["Swamp"]
+            "#,
+        );
+        let local: Vec<bool> = cfg.herbs.iter().map(|herb| cfg.is_local(herb)).collect();
+        assert_eq!(local, [true, false, true]);
+        assert!(cfg.herbs.iter().all(|herb| cfg.rarity_increase(herb) == 0));
+    }
+
+    #[test]
+    fn rejects_negative_import_markup() {
+        let errors = errors(
+            r#"
+            local_biomes = ["Forest"]
+            not_local_rarity_increase = 2
+
+            [biome_distances]
+            Desert = { steps = 0, markup = -3.0 }
+            Swamp = { steps = 1, markup = 0.5 }
+
+            [[rarities]]
+            name = "Common"
+            price_lower = 3
+            price_upper = 20
+            likelihood = 0.75
+
+            [[herbs]]
+            name = "Dried Ephedra"
+            rarity = "Common"
+            biomes = ["Desert"]
+            "#,
+        );
+        assert_eq!(
+            errors,
+            [r#"[biome_distances]: biome "Desert" has markup -3, it must be at least 0"#]
+        );
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let errors = errors(
+            r#"
+            local_biomes = ["Forest"]
+            not_local_rarity_increase = 2
+
+            [[rarities]]
+            name = "Common"
+            price_lower = 20
+            price_upper = 3
+            likelihood = 1.0
+
+            [[herbs]]
+            name = "Arrow Root"
+            rarity = "Common"
+            biomes = ["Forest"]
+
+            [[herbs]]
+            name = "arrow root"
+            rarity = "Common"
+            biomes = ["Forest"]
+            likelihood = 1.5
+            "#,
+        );
+        assert_eq!(
+            errors,
+            [
+                r#"[[rarities]] #1 ("Common"): likelihood is 1, it must be at least 0 and less than 1"#,
+                r#"[[rarities]] #1 ("Common"): price_lower (20) is greater than price_upper (3)"#,
+                r#"[[herbs]] #2 ("arrow root"): name is already used by [[herbs]] #1"#,
+                r#"[[herbs]] #2 ("arrow root"): likelihood is 1.5, it must be at least 0 and less than 1"#,
+            ]
+        );
+    }
+}
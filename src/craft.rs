@@ -0,0 +1,224 @@
+use crate::stock::HerbStock;
+use serde::Deserialize;
+
+/// Something brewed from herbs, e.g. a potion.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    /// Item the recipe makes.
+    pub output: String,
+    pub ingredients: Vec<Ingredient>,
+    /// Check needed to craft the item.
+    pub dc: Option<i64>,
+    /// How long crafting takes, in whatever form the campaign uses, e.g. `1 hour`.
+    pub craft_time: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Ingredient {
+    /// Name of the herb, ignoring case.
+    pub herb: String,
+    #[serde(default = "default_count")]
+    pub count: u16,
+}
+
+fn default_count() -> u16 {
+    1
+}
+
+/// Herbs bought from one market for a recipe.
+pub struct Purchase<'a> {
+    pub herb: &'a str,
+    pub market: &'a str,
+    pub quantity: u16,
+    /// Price per unit in the smallest denomination of the config's currency.
+    pub price: u64,
+}
+
+impl Purchase<'_> {
+    pub fn total(&self) -> u64 {
+        self.price * u64::from(self.quantity)
+    }
+}
+
+/// How to get the ingredients of a recipe from a set of markets.
+pub struct Plan<'a> {
+    pub recipe: &'a Recipe,
+    /// Markets stocking every ingredient, so the recipe can be crafted from a single one.
+    pub craftable_at: Vec<&'a str>,
+    /// Cheapest way to buy the ingredients, taking the cheapest units of every market first.
+    pub shopping_list: Vec<Purchase<'a>>,
+    /// Units of each herb that no market has left.
+    pub missing: Vec<(&'a str, u16)>,
+}
+
+impl Plan<'_> {
+    /// Whether the shopping list has every ingredient.
+    pub fn complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    pub fn cost(&self) -> u64 {
+        self.shopping_list.iter().map(Purchase::total).sum()
+    }
+}
+
+/// Plans buying the ingredients of `recipe` from `markets`, given by name along with their stock.
+pub fn plan<'a>(recipe: &'a Recipe, markets: &[(&'a str, &'a [HerbStock])]) -> Plan<'a> {
+    let offers = |ingredient: &Ingredient| {
+        let mut offers: Vec<(&'a str, &'a HerbStock)> = markets
+            .iter()
+            .filter_map(|&(market, stock)| {
+                stock
+                    .iter()
+                    .find(|herb_stock| herb_stock.herb.name.eq_ignore_ascii_case(&ingredient.herb))
+                    .map(|herb_stock| (market, herb_stock))
+            })
+            .filter(|(_, herb_stock)| herb_stock.quantity > 0)
+            .collect();
+        offers.sort_by_key(|(_, herb_stock)| herb_stock.price);
+        offers
+    };
+    let craftable_at = markets
+        .iter()
+        .filter(|&&(market, _)| {
+            recipe.ingredients.iter().all(|ingredient| {
+                offers(ingredient).iter().any(|&(offer, herb_stock)| {
+                    offer == market && herb_stock.quantity >= ingredient.count
+                })
+            })
+        })
+        .map(|&(market, _)| market)
+        .collect();
+    let mut shopping_list = Vec::new();
+    let mut missing = Vec::new();
+    for ingredient in recipe.ingredients.iter() {
+        let mut needed = ingredient.count;
+        for (market, herb_stock) in offers(ingredient) {
+            if needed == 0 {
+                break;
+            }
+            let quantity = needed.min(herb_stock.quantity);
+            needed -= quantity;
+            shopping_list.push(Purchase {
+                herb: &herb_stock.herb.name,
+                market,
+                quantity,
+                price: herb_stock.price,
+            });
+        }
+        if needed > 0 {
+            missing.push((ingredient.herb.as_str(), needed));
+        }
+    }
+    Plan {
+        recipe,
+        craftable_at,
+        shopping_list,
+        missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Herb;
+
+    fn herb_stock(name: &str, quantity: u16, price: u64) -> HerbStock {
+        let herb: Herb = toml::from_str(&format!(
+            "name = {name:?}\nrarity = \"Common\"\nbiomes = [\"Forest\"]"
+        ))
+        .unwrap();
+        HerbStock {
+            effective_rarity: herb.rarity.clone(),
+            herb,
+            rarity_increase: 0,
+            quantity,
+            price,
+            quantity_roll: None,
+            price_roll: None,
+        }
+    }
+
+    fn recipe(ingredients: &[(&str, u16)]) -> Recipe {
+        Recipe {
+            name: "Potion of Healing".to_string(),
+            output: "Potion of Healing".to_string(),
+            ingredients: ingredients
+                .iter()
+                .map(|&(herb, count)| Ingredient {
+                    herb: herb.to_string(),
+                    count,
+                })
+                .collect(),
+            dc: None,
+            craft_time: None,
+        }
+    }
+
+    #[test]
+    fn buys_cheapest_units_across_markets() {
+        let market = [
+            herb_stock("Wild Sageroot", 2, 300),
+            herb_stock("Mandrake Root", 5, 900),
+        ];
+        let temple = [
+            herb_stock("Wild Sageroot", 4, 200),
+            herb_stock("Arrow Root", 0, 100),
+        ];
+        let markets = [("Market", &market[..]), ("Temple", &temple[..])];
+
+        let potion = recipe(&[("wild sageroot", 5), ("Mandrake Root", 1)]);
+        let potion = plan(&potion, &markets);
+        let purchases: Vec<(&str, &str, u16, u64)> = potion
+            .shopping_list
+            .iter()
+            .map(|purchase| {
+                (
+                    purchase.herb,
+                    purchase.market,
+                    purchase.quantity,
+                    purchase.price,
+                )
+            })
+            .collect();
+        assert_eq!(
+            purchases,
+            [
+                ("Wild Sageroot", "Temple", 4, 200),
+                ("Wild Sageroot", "Market", 1, 300),
+                ("Mandrake Root", "Market", 1, 900),
+            ]
+        );
+        assert_eq!(potion.cost(), 2000);
+        assert!(potion.complete());
+        assert!(potion.craftable_at.is_empty());
+    }
+
+    #[test]
+    fn reports_craftable_markets_and_missing_herbs() {
+        let market = [
+            herb_stock("Wild Sageroot", 2, 300),
+            herb_stock("Mandrake Root", 5, 900),
+        ];
+        let temple = [
+            herb_stock("Wild Sageroot", 4, 200),
+            herb_stock("Arrow Root", 0, 100),
+        ];
+        let markets = [("Market", &market[..]), ("Temple", &temple[..])];
+
+        let potion = recipe(&[("Wild Sageroot", 2), ("Mandrake Root", 1)]);
+        assert_eq!(plan(&potion, &markets).craftable_at, ["Market"]);
+        let salve = recipe(&[("Wild Sageroot", 3)]);
+        assert_eq!(plan(&salve, &markets).craftable_at, ["Temple"]);
+
+        let poultice = recipe(&[("Wild Sageroot", 8), ("Arrow Root", 1), ("Elven Ivy", 2)]);
+        let poultice = plan(&poultice, &markets);
+        assert!(!poultice.complete());
+        assert_eq!(
+            poultice.missing,
+            [("Wild Sageroot", 2), ("Arrow Root", 1), ("Elven Ivy", 2)]
+        );
+        assert_eq!(poultice.cost(), 4 * 200 + 2 * 300);
+    }
+}
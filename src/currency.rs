@@ -0,0 +1,200 @@
+use serde::Deserialize;
+
+/// Coins prices are paid in. Amounts are counted in the smallest denomination, which must be
+/// worth 1.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Currency {
+    /// Denomination prices in the config are in, unless they set `price_unit` themselves.
+    pub price_unit: String,
+    pub denominations: Vec<Denomination>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Denomination {
+    /// Short name used in the config and when showing amounts, e.g. `gp`.
+    pub name: String,
+    /// Worth in the smallest denomination.
+    pub value: u64,
+    /// Whether amounts are broken down into this denomination when shown.
+    #[serde(default = "default_shown")]
+    pub shown: bool,
+}
+
+fn default_shown() -> bool {
+    true
+}
+
+impl Default for Currency {
+    /// Copper, silver, electrum, gold and platinum pieces, showing amounts in gp, sp and cp.
+    fn default() -> Self {
+        let denomination = |name: &str, value, shown| Denomination {
+            name: name.to_string(),
+            value,
+            shown,
+        };
+        Currency {
+            price_unit: "gp".to_string(),
+            denominations: vec![
+                denomination("cp", 1, true),
+                denomination("sp", 10, true),
+                denomination("ep", 50, false),
+                denomination("gp", 100, true),
+                denomination("pp", 1000, false),
+            ],
+        }
+    }
+}
+
+impl Currency {
+    /// Finds a denomination by name, ignoring case.
+    pub fn denomination(&self, name: &str) -> Option<&Denomination> {
+        self.denominations
+            .iter()
+            .find(|denomination| denomination.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Denomination called `unit`, or `price_unit` when `None`.
+    pub fn unit(&self, unit: Option<&str>) -> Option<&Denomination> {
+        self.denomination(unit.unwrap_or(&self.price_unit))
+    }
+
+    /// The denomination worth 1.
+    pub fn smallest(&self) -> Option<&Denomination> {
+        self.denominations
+            .iter()
+            .find(|denomination| denomination.value == 1)
+    }
+
+    /// Shows `amount` broken down into the shown denominations, e.g. `3 gp 5 sp`.
+    pub fn format(&self, amount: u64) -> String {
+        let mut shown: Vec<&Denomination> = self
+            .denominations
+            .iter()
+            .filter(|denomination| denomination.shown)
+            .collect();
+        shown.sort_by_key(|denomination| std::cmp::Reverse(denomination.value));
+        let mut parts = Vec::new();
+        let mut rest = amount;
+        for denomination in shown {
+            let count = rest / denomination.value;
+            if count > 0 {
+                parts.push(format!("{count} {}", denomination.name));
+                rest -= count * denomination.value;
+            }
+        }
+        if rest > 0 || parts.is_empty() {
+            let smallest = self
+                .smallest()
+                .map_or("", |denomination| &denomination.name);
+            parts.push(format!("{rest} {smallest}"));
+        }
+        parts.join(" ")
+    }
+
+    /// `amount` as a possibly fractional number of `denomination`, e.g. `10.5` gp.
+    pub fn in_denomination(&self, amount: u64, denomination: &Denomination) -> f64 {
+        amount as f64 / denomination.value as f64
+    }
+
+    /// Reads an amount such as `3 gp 5 sp` or `12sp`. Bare numbers are in `price_unit`.
+    pub fn parse(&self, s: &str) -> Result<u64, String> {
+        let mut total = 0u64;
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return Err("amount is empty".to_string());
+        }
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let count: u64 = rest[..digits]
+                .parse()
+                .map_err(|_| format!("{s:?} is not an amount like 3 gp 5 sp"))?;
+            rest = rest[digits..].trim_start();
+            let name_len = rest
+                .find(|c: char| c.is_whitespace() || c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let unit = match &rest[..name_len] {
+                "" => None,
+                name => Some(name),
+            };
+            let denomination = self
+                .unit(unit)
+                .ok_or_else(|| format!("unknown denomination {:?}", unit.unwrap_or_default()))?;
+            total = count
+                .checked_mul(denomination.value)
+                .and_then(|value| total.checked_add(value))
+                .ok_or_else(|| format!("{s:?} is too large"))?;
+            rest = rest[name_len..].trim_start();
+        }
+        Ok(total)
+    }
+
+    /// Problems with the denominations, if any.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.smallest().is_none() {
+            problems.push("no denomination has a value of 1".to_string());
+        }
+        for (i, denomination) in self.denominations.iter().enumerate() {
+            if denomination.value == 0 {
+                problems.push(format!(
+                    "denomination {:?} has a value of 0",
+                    denomination.name
+                ));
+            }
+            if self.denominations[..i]
+                .iter()
+                .any(|other| other.name.eq_ignore_ascii_case(&denomination.name))
+            {
+                problems.push(format!(
+                    "denomination {:?} is declared more than once",
+                    denomination.name
+                ));
+            }
+        }
+        if self.denomination(&self.price_unit).is_none() {
+            problems.push(format!(
+                "price_unit {:?} is not a denomination",
+                self.price_unit
+            ));
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_amounts() {
+        let currency = Currency::default();
+        assert_eq!(currency.parse("3 gp 5 sp"), Ok(350));
+        assert_eq!(currency.parse("12sp"), Ok(120));
+        assert_eq!(currency.parse("2 GP 1ep 3cp"), Ok(253));
+        assert_eq!(currency.parse(" 4 "), Ok(400));
+        assert_eq!(currency.parse("1 pp"), Ok(1000));
+    }
+
+    #[test]
+    fn rejects_bad_amounts() {
+        let currency = Currency::default();
+        assert!(currency.parse("").is_err());
+        assert!(currency.parse("gp").is_err());
+        assert!(currency.parse("3 zp").is_err());
+        assert!(currency.parse("-3 gp").is_err());
+        assert!(currency.parse("18446744073709551615 gp").is_err());
+    }
+
+    #[test]
+    fn formats_shown_denominations() {
+        let currency = Currency::default();
+        assert_eq!(currency.format(0), "0 cp");
+        assert_eq!(currency.format(7), "7 cp");
+        assert_eq!(currency.format(350), "3 gp 5 sp");
+        assert_eq!(currency.format(1253), "12 gp 5 sp 3 cp");
+        assert_eq!(currency.format(2000), "20 gp");
+    }
+}
@@ -0,0 +1,358 @@
+use rand::Rng;
+use serde::Deserialize;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// Most dice a single term may roll, to keep typos like `1000000d6` from hanging a run.
+const MAX_DICE: u32 = 1000;
+
+/// A dice expression such as `2d4-1`, `4d6kh3` or `2d6*10+5`.
+///
+/// Terms are added or subtracted. Each is a number or `NdM` dice, where `khK` or `klK` after
+/// the dice keeps only the highest or lowest K of them, and `*N` or `xN` multiplies the term.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Dice {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Term {
+    negative: bool,
+    atom: Atom,
+    multiplier: i64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Atom {
+    Number(i64),
+    Dice {
+        count: u32,
+        sides: u32,
+        keep: Option<Keep>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Keep {
+    Highest(u32),
+    Lowest(u32),
+}
+
+/// Outcome of rolling a [`Dice`] expression.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DiceRoll {
+    pub total: i64,
+    /// Every die rolled, e.g. `4d6kh3 = [6, 4, 3, (1)] = 13`. Dropped dice are in parentheses.
+    pub breakdown: String,
+}
+
+impl Dice {
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> DiceRoll {
+        let mut total = 0;
+        let mut parts = String::new();
+        for (i, term) in self.terms.iter().enumerate() {
+            let (value, part) = term.atom.roll(rng);
+            let value = value * term.multiplier;
+            if term.negative {
+                total -= value;
+            } else {
+                total += value;
+            }
+            match (i, term.negative) {
+                (0, false) => {}
+                (0, true) => parts.push('-'),
+                (_, false) => parts.push_str(" + "),
+                (_, true) => parts.push_str(" - "),
+            }
+            parts.push_str(&part);
+            if term.multiplier != 1 {
+                parts.push_str(&format!("*{}", term.multiplier));
+            }
+        }
+        DiceRoll {
+            total,
+            breakdown: format!("{self} = {parts} = {total}"),
+        }
+    }
+
+    /// Lowest total the expression can roll.
+    pub fn min(&self) -> i64 {
+        self.terms.iter().map(|term| term.bounds().0).sum()
+    }
+
+    /// Highest total the expression can roll.
+    pub fn max(&self) -> i64 {
+        self.terms.iter().map(|term| term.bounds().1).sum()
+    }
+}
+
+impl Term {
+    /// Lowest and highest amount the term adds to a total. Parsing makes sure these fit.
+    fn bounds(&self) -> (i64, i64) {
+        self.checked_bounds()
+            .expect("dice bounds are checked when parsing")
+    }
+
+    fn checked_bounds(&self) -> Option<(i64, i64)> {
+        let low = self.atom.min().checked_mul(self.multiplier)?;
+        let high = self.atom.max().checked_mul(self.multiplier)?;
+        match self.negative {
+            false => Some((low, high)),
+            true => Some((high.checked_neg()?, low.checked_neg()?)),
+        }
+    }
+}
+
+impl Atom {
+    fn roll<R: Rng>(&self, rng: &mut R) -> (i64, String) {
+        match *self {
+            Atom::Number(n) => (n, n.to_string()),
+            Atom::Dice { count, sides, keep } => {
+                let rolls: Vec<u32> = (0..count).map(|_| rng.gen_range(1..=sides)).collect();
+                let mut order: Vec<usize> = (0..rolls.len()).collect();
+                order.sort_by_key(|&i| std::cmp::Reverse(rolls[i]));
+                let kept: Vec<usize> = match keep {
+                    None => order,
+                    Some(Keep::Highest(k)) => order.into_iter().take(k as usize).collect(),
+                    Some(Keep::Lowest(k)) => order.into_iter().rev().take(k as usize).collect(),
+                };
+                let total = kept.iter().map(|&i| i64::from(rolls[i])).sum();
+                let shown: Vec<String> = rolls
+                    .iter()
+                    .enumerate()
+                    .map(|(i, roll)| match kept.contains(&i) {
+                        true => roll.to_string(),
+                        false => format!("({roll})"),
+                    })
+                    .collect();
+                (total, format!("[{}]", shown.join(", ")))
+            }
+        }
+    }
+
+    fn kept(&self) -> i64 {
+        match *self {
+            Atom::Number(_) => 0,
+            Atom::Dice { count, keep, .. } => i64::from(match keep {
+                None => count,
+                Some(Keep::Highest(k) | Keep::Lowest(k)) => k,
+            }),
+        }
+    }
+
+    fn min(&self) -> i64 {
+        match *self {
+            Atom::Number(n) => n,
+            Atom::Dice { .. } => self.kept(),
+        }
+    }
+
+    fn max(&self) -> i64 {
+        match *self {
+            Atom::Number(n) => n,
+            Atom::Dice { sides, .. } => self.kept() * i64::from(sides),
+        }
+    }
+}
+
+impl FromStr for Dice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{s:?} is not a dice expression like 2d6*10+5");
+        let compact: String = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .flat_map(char::to_lowercase)
+            .collect();
+        if compact.is_empty() {
+            return Err(invalid());
+        }
+        let mut terms = Vec::new();
+        let mut rest = compact.as_str();
+        while !rest.is_empty() {
+            let negative = rest.starts_with('-');
+            if negative || rest.starts_with('+') {
+                rest = &rest[1..];
+            } else if !terms.is_empty() {
+                return Err(invalid());
+            }
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let mut factors = rest[..end].split(['*', 'x']);
+            let atom = parse_atom(factors.next().unwrap_or_default()).ok_or_else(invalid)?;
+            let mut multiplier = 1i64;
+            for factor in factors {
+                let factor: i64 = factor.parse().map_err(|_| invalid())?;
+                multiplier = multiplier.checked_mul(factor).ok_or_else(invalid)?;
+            }
+            if let Atom::Dice { count, sides, keep } = atom {
+                if sides == 0 {
+                    return Err(format!("{s:?} rolls dice with no sides"));
+                }
+                if count > MAX_DICE {
+                    return Err(format!("{s:?} rolls more than {MAX_DICE} dice"));
+                }
+                if let Some(Keep::Highest(k) | Keep::Lowest(k)) = keep {
+                    if k > count {
+                        return Err(format!("{s:?} keeps more dice than it rolls"));
+                    }
+                }
+            }
+            terms.push(Term {
+                negative,
+                atom,
+                multiplier,
+            });
+            rest = &rest[end..];
+        }
+        // Every running total while rolling stays within the sum of the terms' largest
+        // magnitudes, so rolling can't overflow once that sum fits.
+        let largest = terms.iter().try_fold(0i64, |sum, term| {
+            let (low, high) = term.checked_bounds()?;
+            let magnitude = i64::try_from(low.unsigned_abs().max(high.unsigned_abs())).ok()?;
+            sum.checked_add(magnitude)
+        });
+        if largest.is_none() {
+            return Err(format!("{s:?} can roll totals too large to count"));
+        }
+        Ok(Dice { terms })
+    }
+}
+
+fn parse_atom(s: &str) -> Option<Atom> {
+    let Some((count, rest)) = s.split_once('d') else {
+        return s.parse().ok().map(Atom::Number);
+    };
+    let count = if count.is_empty() {
+        1
+    } else {
+        count.parse().ok()?
+    };
+    let (sides, keep) = match rest.split_once('k') {
+        Some((sides, keep)) => {
+            let keep = if let Some(k) = keep.strip_prefix('l') {
+                Keep::Lowest(k.parse().ok()?)
+            } else {
+                Keep::Highest(keep.strip_prefix('h').unwrap_or(keep).parse().ok()?)
+            };
+            (sides, Some(keep))
+        }
+        None => (rest, None),
+    };
+    Some(Atom::Dice {
+        count,
+        sides: sides.parse().ok()?,
+        keep,
+    })
+}
+
+impl TryFrom<String> for Dice {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl Display for Dice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            match (i, term.negative) {
+                (0, false) => {}
+                (0, true) => f.write_str("-")?,
+                (_, false) => f.write_str("+")?,
+                (_, true) => f.write_str("-")?,
+            }
+            match term.atom {
+                Atom::Number(n) => write!(f, "{n}")?,
+                Atom::Dice { count, sides, keep } => {
+                    write!(f, "{count}d{sides}")?;
+                    match keep {
+                        Some(Keep::Highest(k)) => write!(f, "kh{k}")?,
+                        Some(Keep::Lowest(k)) => write!(f, "kl{k}")?,
+                        None => {}
+                    }
+                }
+            }
+            if term.multiplier != 1 {
+                write!(f, "*{}", term.multiplier)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn dice(s: &str) -> Dice {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn keep_highest_and_lowest() {
+        let highest = dice("4d6kh3");
+        assert_eq!((highest.min(), highest.max()), (3, 18));
+        assert_eq!(dice("4d6k3"), highest);
+        let lowest = dice("4d6kl1");
+        assert_eq!((lowest.min(), lowest.max()), (1, 6));
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        for _ in 0..100 {
+            let roll = highest.roll(&mut rng);
+            assert!((3..=18).contains(&roll.total));
+            assert_eq!(roll.breakdown.matches('(').count(), 1, "{}", roll.breakdown);
+        }
+    }
+
+    #[test]
+    fn multipliers() {
+        let times = dice("2d6*10+5");
+        assert_eq!((times.min(), times.max()), (25, 125));
+        assert_eq!(dice("2d6x10+5"), times);
+        assert_eq!(dice("3*2*2").max(), 12);
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for _ in 0..100 {
+            let total = times.roll(&mut rng).total;
+            assert_eq!((total - 5) % 10, 0);
+        }
+    }
+
+    #[test]
+    fn negative_terms() {
+        let minus = dice("2d4-1");
+        assert_eq!((minus.min(), minus.max()), (1, 7));
+        let leading = dice("-1d4+10");
+        assert_eq!((leading.min(), leading.max()), (6, 9));
+        assert_eq!(leading.to_string(), "-1d4+10");
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        for _ in 0..100 {
+            assert!((6..=9).contains(&leading.roll(&mut rng).total));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        for s in [
+            "", "2d", "d0", "3d6k4", "2d6+", "2d6**2", "1d6kx", "abc", "1001d6",
+        ] {
+            assert!(s.parse::<Dice>().is_err(), "{s:?} parsed");
+        }
+    }
+
+    #[test]
+    fn rejects_overflow() {
+        for s in [
+            "1d4*9223372036854775807",
+            "1000d1000000*1000000000000",
+            "9223372036854775807+1",
+            "-9223372036854775807-1d2",
+        ] {
+            assert!(s.parse::<Dice>().is_err(), "{s:?} parsed");
+        }
+        assert_eq!(dice("9223372036854775807").max(), i64::MAX);
+    }
+}
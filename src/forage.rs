@@ -0,0 +1,149 @@
+use crate::config::{Biome, Config, Herb, Rarity, RarityConfig, RarityConfigs};
+use crate::dice::Dice;
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// How herbs are gathered in the wild with `forage`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ForageConfig {
+    /// Dice rolled for the skill check when only a modifier is given.
+    #[serde(default = "default_check")]
+    pub check: Dice,
+    /// Check result needed to gather a herb of each rarity. Herbs of rarities without a DC are
+    /// never found.
+    #[serde(default)]
+    pub dc: HashMap<Rarity, i64>,
+}
+
+fn default_check() -> Dice {
+    "1d20".parse().expect("1d20 is a dice expression")
+}
+
+impl Default for ForageConfig {
+    fn default() -> Self {
+        ForageConfig {
+            check: default_check(),
+            dc: HashMap::new(),
+        }
+    }
+}
+
+impl ForageConfig {
+    /// Problems with the DCs, if any.
+    pub fn check(&self, rarities: &RarityConfigs) -> Vec<String> {
+        let mut dc: Vec<&Rarity> = self.dc.keys().collect();
+        dc.sort_by_key(|rarity| rarity.to_string());
+        dc.into_iter()
+            .filter(|rarity| rarities.config(rarity).is_none())
+            .map(|rarity| {
+                format!(
+                    "dc has rarity {:?}, which is not in [[rarities]]",
+                    rarity.to_string()
+                )
+            })
+            .collect()
+    }
+}
+
+/// Skill check of the forager, either the result rolled at the table or a modifier to roll with.
+#[derive(Debug, Clone, Copy)]
+pub enum Check {
+    Result(i64),
+    Modifier(i64),
+}
+
+/// One hour spent searching, and what it turned up.
+pub struct Search<'a> {
+    pub hour: u32,
+    /// Herb the forager came across.
+    pub herb: &'a Herb,
+    pub dc: i64,
+    pub check: i64,
+    /// Dice breakdown of the check, when it was rolled.
+    pub check_roll: Option<String>,
+    /// Units gathered, 0 when the check failed.
+    pub quantity: u16,
+    /// Dice breakdown of the quantity, when it was rolled from a dice expression.
+    pub quantity_roll: Option<String>,
+}
+
+/// Searches `biomes` for `hours`. Each hour turns up one herb growing there, more likely the
+/// more common it is, which is gathered when the check meets the DC of its rarity.
+pub fn forage<'a, R: Rng>(
+    cfg: &'a Config,
+    biomes: &[Biome],
+    hours: u32,
+    check: Check,
+    rng: &mut R,
+) -> Result<Vec<Search<'a>>, String> {
+    let candidates: Vec<(&Herb, &RarityConfig, i64, f32)> = cfg
+        .herbs
+        .iter()
+        .filter(|herb| herb.biomes.iter().any(|biome| biomes.contains(biome)))
+        .filter_map(|herb| {
+            let dc = *cfg.forage.dc.get(&herb.rarity)?;
+            let rarity_config = cfg.rarities.config(&herb.rarity)?;
+            let mut likelihood = herb.likelihood.unwrap_or(rarity_config.likelihood);
+            if let Some(season) = &cfg.season {
+                likelihood *= herb.in_season(season)?.likelihood_multiplier;
+            }
+            Some((herb, rarity_config, dc, likelihood))
+        })
+        .filter(|(_, _, _, likelihood)| *likelihood > 0.0)
+        .collect();
+    let total: f32 = candidates
+        .iter()
+        .map(|(_, _, _, likelihood)| likelihood)
+        .sum();
+    if candidates.is_empty() {
+        let names: Vec<&str> = biomes
+            .iter()
+            .map(|biome| cfg.biomes.display_name(biome))
+            .collect();
+        return Err(format!("no herbs can be foraged in {}", names.join(", ")));
+    }
+    let mut searches = Vec::new();
+    for hour in 1..=hours {
+        let mut roll = rng.gen_range(0.0f32..total);
+        let &(herb, rarity_config, dc, _) = candidates
+            .iter()
+            .find(|(_, _, _, likelihood)| {
+                roll -= likelihood;
+                roll < 0.0
+            })
+            .unwrap_or(&candidates[candidates.len() - 1]);
+        let (check, check_roll) = match check {
+            Check::Result(result) => (result, None),
+            Check::Modifier(modifier) => {
+                let roll = cfg.forage.check.roll(rng);
+                let total = roll.total + modifier;
+                let text = match modifier {
+                    0 => roll.breakdown,
+                    _ if modifier < 0 => format!("{} - {} = {total}", roll.breakdown, -modifier),
+                    _ => format!("{} + {modifier} = {total}", roll.breakdown),
+                };
+                (total, Some(text))
+            }
+        };
+        let (mut quantity, mut quantity_roll) = (0, None);
+        if check >= dc {
+            (quantity, quantity_roll) = rarity_config
+                .quantity
+                .sample(herb.likelihood.unwrap_or(rarity_config.likelihood), rng);
+            if let Some(max_quantity) = herb.max_quantity {
+                quantity = quantity.min(max_quantity);
+            }
+        }
+        searches.push(Search {
+            hour,
+            herb,
+            dc,
+            check,
+            check_roll,
+            quantity,
+            quantity_roll,
+        });
+    }
+    Ok(searches)
+}
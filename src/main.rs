@@ -1,155 +1,548 @@
-use crate::Rarity::*;
-use comfy_table::Table;
-use rand::{thread_rng, Rng};
-use serde::Deserialize;
-use std::collections::HashSet;
+use crate::cli::{Cli, Command, CraftArgs, ForageArgs, Format, GenerateArgs, ShowArgs, TradeArgs};
+use crate::config::{Config, Distance, DistanceTier, MerchantConfig, Settlement};
+use crate::craft::{Plan, Recipe};
+use crate::forage::Check;
+use crate::output::{Availability, Market};
+use crate::seed::Seed;
+use crate::state::{MarketState, Trade};
+use crate::stock::{generate_stock, herb_stocking, HerbStock};
 use std::error::Error;
-use std::fs::read_to_string;
-use std::ops::RangeInclusive;
-
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize)]
-enum Rarity {
-    Common,
-    Uncommon,
-    Rare,
-    VeryRare,
-}
-
-impl Rarity {
-    fn next_rarity(self) -> Option<Self> {
-        match self {
-            Common => Some(Uncommon),
-            Uncommon => Some(Rare),
-            Rare => Some(VeryRare),
-            VeryRare => None,
+use std::fs::OpenOptions;
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+mod calendar;
+mod cli;
+mod config;
+mod craft;
+mod currency;
+mod dice;
+mod forage;
+mod output;
+mod quantity;
+mod seed;
+mod state;
+mod stock;
+
+/// Applies the locality, rarity, size, season and event overrides of `args` to `cfg`.
+fn apply_overrides(cfg: &mut Config, args: &GenerateArgs) -> Result<(), String> {
+    if !args.biomes.is_empty() || !args.nearby_biomes.is_empty() {
+        let resolve = |name: &String| match cfg.biomes.resolve(name) {
+            Some(biome_config) => Ok(biome_config.name.clone()),
+            None => Err(format!("unknown biome {name:?}")),
+        };
+        cfg.local_biomes = args.biomes.iter().map(resolve).collect::<Result<_, _>>()?;
+        cfg.biome_distances = args
+            .nearby_biomes
+            .iter()
+            .map(|name| Ok((resolve(name)?, Distance::Tier(DistanceTier::Nearby))))
+            .collect::<Result<_, String>>()?;
+    }
+    if let Some(rarity_shift) = args.rarity_shift {
+        cfg.not_local_rarity_increase = rarity_shift;
+    }
+    if let Some(size) = &args.size {
+        cfg.size = Some(size.clone());
+        if cfg.size_config().is_none() {
+            return Err(format!("unknown size {size:?}"));
+        }
+    }
+    if let Some(date) = &args.date {
+        if let Some(season) = cfg.calendar.season_of(date) {
+            cfg.season = Some(season.name.clone());
         }
     }
+    if let Some(season) = &args.season {
+        let season = cfg
+            .calendar
+            .season(season)
+            .ok_or_else(|| format!("unknown season {season:?}"))?;
+        cfg.season = Some(season.name.clone());
+    }
+    if let Some(event) = &args.event {
+        cfg.event = Some(event.clone());
+        if cfg.event_config().is_none() {
+            return Err(format!("unknown event {event:?}"));
+        }
+    }
+    Ok(())
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize)]
-enum Biome {
-    MostTerrain,
-    Coastal,
-    Underdark,
-    Desert,
-    Mountain,
-    Swamp,
-    Forest,
-    Arctic,
-    Hills,
-    Grasslands,
+/// Rolls an event for the market `seed` generates from `cfg`, unless it already has one. Each
+/// town rolls from a seed derived for it, and the stock is left as it was without events.
+fn roll_event(cfg: &mut Config, seed: &Seed, town: Option<&Settlement>) {
+    if cfg.event.is_some() {
+        return;
+    }
+    let seed = match town {
+        Some(town) => seed.derive(&town.name),
+        None => seed.clone(),
+    };
+    let mut rng = seed.derive("event").rng();
+    cfg.event = stock::roll_event(cfg, &mut rng).map(|event| event.name.clone());
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize)]
-struct Herb {
-    name: String,
-    rarity: Rarity,
-    biomes: Vec<Biome>,
+/// Stock of the market `seed` generates from `cfg`, sorted by name. Towns and merchants
+/// generate from a seed derived for them, so each gets its own stock.
+fn generate_sorted(
+    cfg: &Config,
+    seed: &Seed,
+    town: Option<&Settlement>,
+    merchant: Option<&MerchantConfig>,
+) -> Vec<HerbStock> {
+    let mut seed = seed.clone();
+    if let Some(town) = town {
+        seed = seed.derive(&town.name);
+    }
+    if let Some(merchant) = merchant {
+        seed = seed.derive(&merchant.name);
+    }
+    let mut rng = seed.rng();
+    let mut stock = generate_stock(cfg, merchant, &mut rng);
+    stock.sort_by_key(|herb_stock| herb_stock.herb.name.clone());
+    stock
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-struct RarityConfig {
-    price_lower: u16,
-    price_upper: u16,
-    likelihood: f32,
+/// A generated market: its name, the config it was generated from, the merchant running it and
+/// its stock.
+type Generated<'a> = (String, Config, Option<&'a MerchantConfig>, Vec<HerbStock>);
+
+/// Merchants whose shops make up the market of `town`, or just the town's own market when it
+/// lists none.
+fn shops<'a>(cfg: &'a Config, town: &Settlement) -> Vec<Option<&'a MerchantConfig>> {
+    match town.merchants.is_empty() {
+        true => vec![None],
+        false => town
+            .merchants
+            .iter()
+            .map(|name| cfg.merchant(name))
+            .collect(),
+    }
 }
 
-impl RarityConfig {
-    fn price_range(&self) -> RangeInclusive<u16> {
-        self.price_lower..=self.price_upper
+/// Generates the markets `args` asks for from `cfg`, saving the market with `--save`.
+fn generate_markets<'a>(
+    config: &Path,
+    cfg: &'a Config,
+    args: &GenerateArgs,
+    seed: &Seed,
+) -> Result<Vec<Generated<'a>>, Box<dyn Error>> {
+    let towns: Vec<Option<&Settlement>> = match &args.town {
+        _ if args.all_towns => {
+            if cfg.settlements.is_empty() {
+                return Err("the config has no [[settlements]]".into());
+            }
+            cfg.settlements.iter().map(Some).collect()
+        }
+        Some(name) => vec![Some(
+            cfg.settlement(name)
+                .ok_or_else(|| format!("unknown town {name:?}"))?,
+        )],
+        None => vec![None],
+    };
+
+    let merchants = args
+        .merchants
+        .iter()
+        .map(|name| {
+            cfg.merchant(name)
+                .ok_or_else(|| format!("unknown merchant {name:?}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut generated = Vec::new();
+    for town in towns {
+        let mut town_cfg = town.map_or_else(|| cfg.clone(), |town| cfg.at(town));
+        apply_overrides(&mut town_cfg, args)?;
+        roll_event(&mut town_cfg, seed, town);
+        let town_merchants: Vec<Option<&MerchantConfig>> = match town {
+            _ if !merchants.is_empty() => merchants.iter().copied().map(Some).collect(),
+            Some(town) => shops(cfg, town),
+            None => vec![None],
+        };
+        let market_name = match (&args.market, town) {
+            (Some(name), _) => name.clone(),
+            (None, Some(town)) => town.name.clone(),
+            (None, None) => config
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        for merchant in town_merchants {
+            let stock = generate_sorted(&town_cfg, seed, town, merchant);
+            let name = match merchant {
+                Some(merchant) => format!("{market_name} - {}", merchant.name),
+                None => market_name.clone(),
+            };
+            generated.push((name, town_cfg.clone(), merchant, stock));
+        }
     }
+    if let Some(path) = &args.save {
+        let [(name, cfg, merchant, stock)] = generated.as_slice() else {
+            return Err("--save needs a single market, pick one shop with --merchant".into());
+        };
+        MarketState::new(name.clone(), seed, cfg, *merchant, stock).save(path)?;
+    }
+    Ok(generated)
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-struct RarityConfigs {
-    common: RarityConfig,
-    uncommon: RarityConfig,
-    rare: RarityConfig,
-    very_rare: RarityConfig,
+/// Checks `--append` is only given for output that can be appended to, before anything is
+/// generated or saved.
+fn check_append(args: &GenerateArgs) -> Result<(), Box<dyn Error>> {
+    match args.append && !matches!(args.format, Format::Csv | Format::Tsv) {
+        true => Err("--append needs csv or tsv output, other formats can't be joined".into()),
+        false => Ok(()),
+    }
 }
 
-impl RarityConfigs {
-    fn config(&self, rarity: Rarity) -> &RarityConfig {
-        match rarity {
-            Common => &self.common,
-            Uncommon => &self.uncommon,
-            Rare => &self.rare,
-            VeryRare => &self.very_rare,
+/// Runs `write` on the file given with `--output`, or on stdout, telling it whether the output
+/// is empty and needs a header.
+fn write_output(
+    args: &GenerateArgs,
+    write: impl FnOnce(&mut dyn Write, bool) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    match &args.output {
+        Some(path) => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .append(args.append)
+                .truncate(!args.append)
+                .open(path)
+                .map_err(|e| format!("failed to open {}: {e}", path.display()))?;
+            let header = file.metadata()?.len() == 0;
+            write(&mut file, header)
         }
+        None => write(&mut stdout().lock(), true),
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-struct Config {
-    local_biomes: HashSet<Biome>,
-    not_local_rarity_increase: u8,
-    rarities: RarityConfigs,
-    herbs: Vec<Herb>,
+fn generate(config: &Path, args: GenerateArgs) -> Result<(), Box<dyn Error>> {
+    check_append(&args)?;
+    let cfg = Config::load(config)?;
+    let seed = args
+        .seed
+        .clone()
+        .map(Seed::from)
+        .unwrap_or_else(Seed::random);
+    let generated = generate_markets(config, &cfg, &args, &seed)?;
+    let markets: Vec<Market> = generated
+        .iter()
+        .map(|(name, cfg, _, stock)| Market {
+            verbose: args.verbose,
+            name: name.as_str(),
+            date: args.date.as_deref(),
+            seed: &seed,
+            config,
+            cfg,
+            stock,
+        })
+        .collect();
+    write_output(&args, |out, header| {
+        output::write(out, args.format, &markets, header)
+    })
 }
 
-struct HerbStock {
-    herb: Herb,
-    quantity: u16,
-    price: u16,
+/// Plans the `[[recipes]]` of the config, or only `args.recipe`, against a saved market or the
+/// markets `args` generates.
+fn craft(config: &Path, args: CraftArgs) -> Result<(), Box<dyn Error>> {
+    check_append(&args.market)?;
+    let cfg = Config::load(config)?;
+    let recipes: Vec<&Recipe> = match &args.recipe {
+        Some(name) => vec![cfg
+            .recipes
+            .iter()
+            .find(|recipe| recipe.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("unknown recipe {name:?}"))?],
+        None if cfg.recipes.is_empty() => return Err("the config has no [[recipes]]".into()),
+        None => cfg.recipes.iter().collect(),
+    };
+    let (seed, markets, saved) = match &args.state {
+        Some(path) => {
+            let state = MarketState::load(path)?;
+            let mut state_cfg = cfg.clone();
+            state.apply_locality(&mut state_cfg);
+            let stock = state.stock(&state_cfg)?;
+            let markets = vec![(state.name.clone(), stock)];
+            (
+                Seed::from(state.seed.clone()),
+                markets,
+                Some((state, state_cfg)),
+            )
+        }
+        None => {
+            let seed = args
+                .market
+                .seed
+                .clone()
+                .map(Seed::from)
+                .unwrap_or_else(Seed::random);
+            let markets = generate_markets(config, &cfg, &args.market, &seed)?
+                .into_iter()
+                .map(|(name, _, _, stock)| (name, stock))
+                .collect();
+            (seed, markets, None)
+        }
+    };
+    let markets: Vec<(&str, &[HerbStock])> = markets
+        .iter()
+        .map(|(name, stock)| (name.as_str(), stock.as_slice()))
+        .collect();
+    let mut plans: Vec<Plan> = recipes
+        .into_iter()
+        .map(|recipe| craft::plan(recipe, &markets))
+        .collect();
+    if let Some((state, state_cfg)) = &saved {
+        for plan in plans.iter_mut() {
+            state.quote(state_cfg, &mut plan.shopping_list)?;
+        }
+    }
+    write_output(&args.market, |out, header| {
+        output::write_crafting(out, args.market.format, &seed, &cfg, &plans, header)
+    })
 }
 
-fn generate_stock<R: Rng>(cfg: &Config, rng: &mut R) -> Vec<HerbStock> {
-    let mut stock = Vec::new();
-    'herb: for herb in cfg.herbs.iter() {
-        let is_local = herb.biomes.iter().any(|b| cfg.local_biomes.contains(b));
-        let effective_rarity = if is_local {
-            herb.rarity
-        } else {
-            let mut tmp = herb.rarity;
-            for _ in 0..cfg.not_local_rarity_increase {
-                if let Some(effective_rarity) = tmp.next_rarity() {
-                    tmp = effective_rarity;
-                } else {
-                    continue 'herb;
-                }
-            }
-            tmp
-        };
-        let rarity_config = cfg.rarities.config(effective_rarity);
-        let mut quantity = 0;
-        while rng.gen_range(0.0f32..1.0f32) < rarity_config.likelihood {
-            quantity += 1;
-        }
-        if quantity == 0 {
-            continue;
+fn compare(
+    config: &Path,
+    herb: &str,
+    seed: Option<String>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let cfg = Config::load(config)?;
+    if cfg.settlements.is_empty() {
+        return Err("the config has no [[settlements]]".into());
+    }
+    let herb = cfg
+        .herb(herb)
+        .ok_or_else(|| format!("unknown herb {herb:?}"))?;
+    let seed = seed.map(Seed::from).unwrap_or_else(Seed::random);
+    let mut availability: Vec<Availability> = Vec::new();
+    for town in cfg.settlements.iter() {
+        let mut town_cfg = cfg.at(town);
+        roll_event(&mut town_cfg, &seed, Some(town));
+        let event = cfg
+            .events
+            .iter()
+            .find(|event| town_cfg.event.as_ref() == Some(&event.name))
+            .map(|event| event.name.as_str());
+        for merchant in shops(&cfg, town) {
+            let stocking = herb_stocking(&town_cfg, merchant, herb);
+            let herb_stock = generate_sorted(&town_cfg, &seed, Some(town), merchant)
+                .into_iter()
+                .find(|herb_stock| herb_stock.herb.name == herb.name);
+            availability.push(Availability {
+                town: town.name.as_str(),
+                merchant: merchant.map(|merchant| merchant.name.as_str()),
+                rarity_increase: town_cfg.rarity_increase(herb),
+                effective_rarity: stocking
+                    .as_ref()
+                    .map(|stocking| stocking.rarity_config.name.clone()),
+                likelihood: stocking.map_or(0.0, |stocking| stocking.likelihood),
+                quantity: herb_stock
+                    .as_ref()
+                    .map_or(0, |herb_stock| herb_stock.quantity),
+                price: herb_stock.map(|herb_stock| herb_stock.price),
+                event,
+            });
         }
-        let price = rng.gen_range(rarity_config.price_range());
-        stock.push(HerbStock {
-            herb: herb.clone(),
-            quantity,
-            price,
-        });
     }
-    stock
+    output::write_comparison(
+        &mut stdout().lock(),
+        format,
+        &seed,
+        &cfg,
+        &herb.name,
+        &availability,
+    )
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let cfg_text = read_to_string("herb-market.config.toml")?;
-    let cfg: Config = toml::from_str(cfg_text.as_str())?;
+fn show(
+    config: &Path,
+    args: ShowArgs,
+    days: u32,
+    season: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let mut cfg = Config::load(config)?;
+    let mut state = MarketState::load(&args.state)?;
+    let changed = days > 0 || season.is_some();
+    if let Some(season) = season {
+        let season = cfg
+            .calendar
+            .season(&season)
+            .ok_or_else(|| format!("unknown season {season:?}"))?;
+        state.season = Some(season.name.clone());
+    }
+    state.apply_locality(&mut cfg);
+    state.advance(&cfg, days)?;
+    if changed {
+        state.save(&args.state)?;
+    }
+    let stock = state.stock(&cfg)?;
+    let seed = Seed::from(state.seed.clone());
+    let date = format!("Day {}", state.day);
+    let market = Market {
+        verbose: args.verbose,
+        name: state.name.as_str(),
+        date: Some(date.as_str()),
+        seed: &seed,
+        config,
+        cfg: &cfg,
+        stock: &stock,
+    };
+    output::write(&mut stdout().lock(), args.format, &[market], true)
+}
 
-    let mut table = Table::new();
-    table.set_header(["Herb", "Quantity", "Price (gp)"]);
+fn trade(config: &Path, args: TradeArgs, trade: Trade) -> Result<(), Box<dyn Error>> {
+    let mut cfg = Config::load(config)?;
+    let mut state = MarketState::load(&args.state)?;
+    state.apply_locality(&mut cfg);
+    let entry = match trade {
+        Trade::Buy => state.buy(&cfg, &args.herb, args.quantity, &args.customer)?,
+        Trade::Sell => state.sell(&cfg, &args.herb, args.quantity, &args.customer)?,
+    };
+    println!(
+        "Day {}: {} {} {} {} for {} ({} each)",
+        entry.day,
+        entry.customer,
+        match trade {
+            Trade::Buy => "bought",
+            Trade::Sell => "sold",
+        },
+        entry.quantity,
+        entry.herb,
+        cfg.currency.format(entry.total()),
+        cfg.currency.format(entry.price),
+    );
+    state.save(&args.state)
+}
 
-    let mut rng = thread_rng();
+fn ledger(
+    config: &Path,
+    state: &Path,
+    customer: Option<&str>,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    let cfg = Config::load(config)?;
+    let state = MarketState::load(state)?;
+    let entries: Vec<_> = state
+        .ledger
+        .iter()
+        .filter(|entry| {
+            customer.is_none_or(|customer| entry.customer.eq_ignore_ascii_case(customer))
+        })
+        .collect();
+    output::write_ledger(&mut stdout().lock(), format, &state.name, &cfg, &entries)
+}
 
-    let mut stock = generate_stock(&cfg, &mut rng);
-    stock.sort_by_key(|herb_stock| herb_stock.herb.name.clone());
+fn forage(config: &Path, args: ForageArgs) -> Result<(), Box<dyn Error>> {
+    let mut cfg = Config::load(config)?;
+    if cfg.forage.dc.is_empty() {
+        return Err("the config has no [forage] dc".into());
+    }
+    let biomes = args
+        .biomes
+        .iter()
+        .map(|name| match cfg.biomes.resolve(name) {
+            Some(biome_config) => Ok(biome_config.name.clone()),
+            None => Err(format!("unknown biome {name:?}")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(season) = &args.season {
+        let season = cfg
+            .calendar
+            .season(season)
+            .ok_or_else(|| format!("unknown season {season:?}"))?;
+        cfg.season = Some(season.name.clone());
+    }
+    let check = match args.check {
+        Some(result) => Check::Result(result),
+        None => Check::Modifier(args.modifier),
+    };
+    let seed = args.seed.map(Seed::from).unwrap_or_else(Seed::random);
+    let searches = forage::forage(&cfg, &biomes, args.hours, check, &mut seed.rng())?;
+    output::write_forage(
+        &mut stdout().lock(),
+        args.format,
+        &seed,
+        &cfg,
+        &biomes,
+        &searches,
+        args.verbose,
+    )
+}
+
+fn describe(config: &Path, herb: &str, format: Format) -> Result<(), Box<dyn Error>> {
+    let cfg = Config::load(config)?;
+    let herb = cfg
+        .herb(herb)
+        .ok_or_else(|| format!("unknown herb {herb:?}"))?;
+    output::write_herb(&mut stdout().lock(), format, &cfg, herb)
+}
+
+fn validate(config: &Path) -> Result<(), Box<dyn Error>> {
+    let cfg = Config::load(config)?;
+    println!("{} is valid, {} herbs", config.display(), cfg.herbs.len());
+    Ok(())
+}
 
-    for herb_stock in stock {
-        table.add_row([
-            herb_stock.herb.name.as_str(),
-            format!("{}", herb_stock.quantity).as_str(),
-            format!("{}", herb_stock.price).as_str(),
-        ]);
+fn convert(config: &Path, amounts: &[String]) -> Result<(), Box<dyn Error>> {
+    let cfg = Config::load(config)?;
+    let currency = &cfg.currency;
+    let mut total = 0u64;
+    for amount in amounts {
+        total = total
+            .checked_add(currency.parse(amount)?)
+            .ok_or("total is too large")?;
     }
-    println!("```");
-    println!("{table}");
-    println!("```");
+    println!("Total: {}", currency.format(total));
+    let mut denominations: Vec<_> = currency.denominations.iter().collect();
+    denominations.sort_by_key(|denomination| denomination.value);
+    for denomination in denominations {
+        println!(
+            "  = {} {}",
+            currency.in_denomination(total, denomination),
+            denomination.name
+        );
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse_args();
+
+    let result = match cli
+        .command
+        .unwrap_or(Command::Generate(Box::new(cli.generate)))
+    {
+        Command::Generate(args) => generate(&cli.config, *args),
+        Command::Validate => validate(&cli.config),
+        Command::Convert { amounts } => convert(&cli.config, &amounts),
+        Command::Show(args) => show(&cli.config, args, 0, None),
+        Command::Advance {
+            show: args,
+            days,
+            season,
+        } => show(&cli.config, args, days, season),
+        Command::Compare { herb, seed, format } => compare(&cli.config, &herb, seed, format),
+        Command::Describe { herb, format } => describe(&cli.config, &herb, format),
+        Command::Forage(args) => forage(&cli.config, args),
+        Command::Craft(args) => craft(&cli.config, *args),
+        Command::Buy(args) => trade(&cli.config, args, Trade::Buy),
+        Command::Sell(args) => trade(&cli.config, args, Trade::Sell),
+        Command::Ledger {
+            state,
+            customer,
+            format,
+        } => ledger(&cli.config, &state, customer.as_deref(), format),
+    };
+    let exit_code = match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    };
 
     #[cfg(target_os = "windows")]
     let _ = std::process::Command::new("cmd.exe")
@@ -157,5 +550,5 @@ fn main() -> Result<(), Box<dyn Error>> {
         .arg("pause")
         .status();
 
-    Ok(())
+    exit_code
 }
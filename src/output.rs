@@ -0,0 +1,1081 @@
+use crate::cli::Format;
+use crate::config::{Biome, Config, Herb, Rarity, Source};
+use crate::craft::Plan;
+use crate::forage::Search;
+use crate::seed::Seed;
+use crate::state::{LedgerEntry, Trade};
+use crate::stock::HerbStock;
+use comfy_table::Table;
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+/// Version of the JSON document printed by `--format json`.
+///
+/// Fields may be added without notice, but renaming, removing or changing the meaning of one
+/// bumps this number.
+const JSON_SCHEMA_VERSION: u32 = 3;
+
+/// A generated market along with what is needed to reproduce it.
+pub struct Market<'a> {
+    /// Whether tables get columns with the details of each herb and the dice rolled for it.
+    pub verbose: bool,
+    pub name: &'a str,
+    pub date: Option<&'a str>,
+    pub seed: &'a Seed,
+    pub config: &'a Path,
+    pub cfg: &'a Config,
+    pub stock: &'a [HerbStock],
+}
+
+/// Top level of the JSON output.
+#[derive(Serialize)]
+struct JsonMarkets<'a> {
+    schema_version: u32,
+    /// Every market generated, even when there is only one.
+    markets: Vec<JsonMarket<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonMarket<'a> {
+    /// Name of the market, the config file name unless given with `--market`.
+    market: &'a str,
+    /// Date given with `--date`, or null.
+    date: Option<&'a str>,
+    /// Season herbs were stocked for, or null.
+    season: Option<&'a str>,
+    /// Event happening at the market, or null.
+    event: Option<JsonEvent<'a>>,
+    /// The seed exactly as given, or the random one picked for this run.
+    seed: String,
+    /// Path of the config the market was generated from.
+    config: String,
+    /// Biomes treated as local, sorted.
+    local_biomes: Vec<Biome>,
+    /// Smallest denomination of the currency, which `price` is counted in.
+    currency_unit: &'a str,
+    /// Herbs in stock, sorted by name.
+    stock: Vec<JsonHerbStock<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonEvent<'a> {
+    name: &'a str,
+    description: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonHerbStock<'a> {
+    name: &'a str,
+    /// Rarity of the herb as written in the config.
+    rarity: Rarity,
+    /// Rarity the herb was stocked at, after the non-local increase.
+    effective_rarity: Rarity,
+    biomes: &'a [Biome],
+    /// Whether any of the herb's biomes is local to the market.
+    local: bool,
+    /// Rarity steps added because none of the herb's biomes is local, going by the closest.
+    rarity_increase: u8,
+    quantity: u16,
+    /// Price per unit in `currency_unit`.
+    price: u64,
+    /// Price per unit broken down into denominations, e.g. `"3 gp 5 sp"`.
+    price_text: String,
+    /// Dice rolled for the quantity, e.g. `"2d4-1 = [3, 1]-1 = 3"`, or null when the tier
+    /// doesn't use dice.
+    quantity_roll: Option<&'a str>,
+    /// Dice rolled for the price, or null when the tier doesn't use dice.
+    price_roll: Option<&'a str>,
+    tags: &'a [String],
+    /// Weight of one unit in pounds, or null.
+    weight: Option<f32>,
+    effect: Option<&'a str>,
+    description: Option<&'a str>,
+    /// Book the herb comes from, or null.
+    source: Option<&'a Source>,
+}
+
+/// Writes `markets` to `out`. `header` controls whether CSV and TSV output starts with a
+/// header row, so that several markets can be appended to one file. JSON output is a single
+/// document listing every market.
+pub fn write(
+    out: &mut dyn Write,
+    format: Format,
+    markets: &[Market],
+    header: bool,
+) -> Result<(), Box<dyn Error>> {
+    let titled = markets.len() > 1;
+    match format {
+        Format::Markdown => {
+            for market in markets {
+                if titled {
+                    writeln!(out, "## {}", market.name)?;
+                }
+                write_summary(out, market)?;
+                writeln!(out, "```")?;
+                writeln!(out, "{}", table(market))?;
+                writeln!(out, "```")?;
+            }
+        }
+        Format::Table => {
+            for market in markets {
+                if titled {
+                    writeln!(out, "{}", market.name)?;
+                }
+                write_summary(out, market)?;
+                writeln!(out, "{}", table(market))?;
+            }
+        }
+        Format::Json => {
+            let json = JsonMarkets {
+                schema_version: JSON_SCHEMA_VERSION,
+                markets: markets.iter().map(json).collect(),
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&json)?)?;
+        }
+        Format::Csv => delimited(out, b',', markets, header)?,
+        Format::Tsv => delimited(out, b'\t', markets, header)?,
+    }
+    Ok(())
+}
+
+/// Seed, season and event of `market`, shown above its table.
+fn write_summary(out: &mut dyn Write, market: &Market) -> Result<(), Box<dyn Error>> {
+    writeln!(out, "Seed: {}", market.seed)?;
+    if let Some(season) = &market.cfg.season {
+        writeln!(out, "Season: {season}")?;
+    }
+    if let Some(event) = market.cfg.event_config() {
+        match event.description.is_empty() {
+            true => writeln!(out, "Event: {}", event.name)?,
+            false => writeln!(out, "Event: {}: {}", event.name, event.description)?,
+        }
+    }
+    Ok(())
+}
+
+fn table(market: &Market) -> Table {
+    let mut table = Table::new();
+    let effects = market
+        .stock
+        .iter()
+        .any(|herb_stock| herb_stock.herb.effect.is_some());
+    let mut header = vec!["Herb", "Quantity", "Price"];
+    if effects {
+        header.push("Effect");
+    }
+    let details = market.verbose
+        && market
+            .stock
+            .iter()
+            .any(|herb_stock| !herb_details(&herb_stock.herb).is_empty());
+    if details {
+        header.push("Details");
+    }
+    if market.verbose {
+        header.push("Rolls");
+    }
+    table.set_header(header);
+    for herb_stock in market.stock {
+        let mut row = vec![
+            herb_stock.herb.name.clone(),
+            format!("{}", herb_stock.quantity),
+            market.cfg.currency.format(herb_stock.price),
+        ];
+        if effects {
+            row.push(herb_stock.herb.effect.clone().unwrap_or_default());
+        }
+        if details {
+            row.push(herb_details(&herb_stock.herb).join("\n"));
+        }
+        if market.verbose {
+            let rolls: Vec<String> = [
+                herb_stock
+                    .quantity_roll
+                    .as_ref()
+                    .map(|roll| format!("Quantity: {roll}")),
+                herb_stock
+                    .price_roll
+                    .as_ref()
+                    .map(|roll| format!("Price: {roll}")),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            row.push(rolls.join("\n"));
+        }
+        table.add_row(row);
+    }
+    table
+}
+
+/// Tags, weight, source and description of `herb`, one line each, leaving out what it lacks.
+fn herb_details(herb: &Herb) -> Vec<String> {
+    let mut details = Vec::new();
+    if !herb.tags.is_empty() {
+        details.push(format!("Tags: {}", herb.tags.join(", ")));
+    }
+    if let Some(weight) = herb.weight {
+        details.push(format!("Weight: {weight} lb"));
+    }
+    if let Some(source) = &herb.source {
+        details.push(format!("Source: {source}"));
+    }
+    details.extend(herb.description.clone());
+    details
+}
+
+fn json<'a>(market: &Market<'a>) -> JsonMarket<'a> {
+    let mut local_biomes: Vec<Biome> = market.cfg.local_biomes.iter().cloned().collect();
+    local_biomes.sort();
+    JsonMarket {
+        market: market.name,
+        date: market.date,
+        season: market.cfg.season.as_deref(),
+        event: market.cfg.event_config().map(|event| JsonEvent {
+            name: &event.name,
+            description: &event.description,
+        }),
+        seed: market.seed.to_string(),
+        config: market.config.display().to_string(),
+        local_biomes,
+        currency_unit: market
+            .cfg
+            .currency
+            .smallest()
+            .map_or("", |denomination| denomination.name.as_str()),
+        stock: market
+            .stock
+            .iter()
+            .map(|herb_stock| JsonHerbStock {
+                name: herb_stock.herb.name.as_str(),
+                rarity: herb_stock.herb.rarity.clone(),
+                effective_rarity: herb_stock.effective_rarity.clone(),
+                biomes: herb_stock.herb.biomes.as_slice(),
+                local: market.cfg.is_local(&herb_stock.herb),
+                rarity_increase: herb_stock.rarity_increase,
+                quantity: herb_stock.quantity,
+                price: herb_stock.price,
+                price_text: market.cfg.currency.format(herb_stock.price),
+                quantity_roll: herb_stock.quantity_roll.as_deref(),
+                price_roll: herb_stock.price_roll.as_deref(),
+                tags: &herb_stock.herb.tags,
+                weight: herb_stock.herb.weight,
+                effect: herb_stock.herb.effect.as_deref(),
+                description: herb_stock.herb.description.as_deref(),
+                source: herb_stock.herb.source.as_ref(),
+            })
+            .collect(),
+    }
+}
+
+fn delimited(
+    out: &mut dyn Write,
+    delimiter: u8,
+    markets: &[Market],
+    header: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .quote_style(csv::QuoteStyle::NonNumeric)
+        .from_writer(out);
+    let Some(first) = markets.first() else {
+        return Ok(());
+    };
+    let price_unit = first
+        .cfg
+        .currency
+        .unit(None)
+        .ok_or("currency has no price_unit")?;
+    if header {
+        writer.write_record([
+            "Market",
+            "Date",
+            "Seed",
+            "Herb",
+            "Quantity",
+            format!("Price ({})", price_unit.name).as_str(),
+            "Rarity",
+            "Effective Rarity",
+            "Biomes",
+            "Local",
+            "Rarity Increase",
+            "Price Text",
+            "Tags",
+            "Weight",
+            "Effect",
+            "Description",
+            "Source",
+        ])?;
+    }
+    for market in markets {
+        let currency = &market.cfg.currency;
+        let seed = market.seed.to_string();
+        for herb_stock in market.stock {
+            let biomes: Vec<&str> = herb_stock
+                .herb
+                .biomes
+                .iter()
+                .map(|biome| market.cfg.biomes.display_name(biome))
+                .collect();
+            writer.write_record([
+                market.name,
+                market.date.unwrap_or_default(),
+                seed.as_str(),
+                herb_stock.herb.name.as_str(),
+                herb_stock.quantity.to_string().as_str(),
+                currency
+                    .in_denomination(herb_stock.price, price_unit)
+                    .to_string()
+                    .as_str(),
+                herb_stock.herb.rarity.to_string().as_str(),
+                herb_stock.effective_rarity.to_string().as_str(),
+                biomes.join("; ").as_str(),
+                market.cfg.is_local(&herb_stock.herb).to_string().as_str(),
+                herb_stock.rarity_increase.to_string().as_str(),
+                currency.format(herb_stock.price).as_str(),
+                herb_stock.herb.tags.join("; ").as_str(),
+                herb_stock
+                    .herb
+                    .weight
+                    .map(|weight| weight.to_string())
+                    .unwrap_or_default()
+                    .as_str(),
+                herb_stock.herb.effect.as_deref().unwrap_or_default(),
+                herb_stock.herb.description.as_deref().unwrap_or_default(),
+                herb_stock
+                    .herb
+                    .source
+                    .as_ref()
+                    .map(Source::to_string)
+                    .unwrap_or_default()
+                    .as_str(),
+            ])?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes everything the config says about `herb` to `out`.
+pub fn write_herb(
+    out: &mut dyn Write,
+    format: Format,
+    cfg: &Config,
+    herb: &Herb,
+) -> Result<(), Box<dyn Error>> {
+    let biomes: Vec<&str> = herb
+        .biomes
+        .iter()
+        .map(|biome| cfg.biomes.display_name(biome))
+        .collect();
+    let mut fields = vec![
+        ("Rarity", herb.rarity.to_string()),
+        ("Biomes", biomes.join(", ")),
+    ];
+    if !herb.tags.is_empty() {
+        fields.push(("Tags", herb.tags.join(", ")));
+    }
+    if let Some(weight) = herb.weight {
+        fields.push(("Weight", format!("{weight} lb")));
+    }
+    if let Some(source) = &herb.source {
+        fields.push(("Source", source.to_string()));
+    }
+    if let Some(effect) = &herb.effect {
+        fields.push(("Effect", effect.clone()));
+    }
+    match format {
+        Format::Markdown => {
+            writeln!(out, "## {}", herb.name)?;
+            for (field, value) in fields {
+                writeln!(out, "- **{field}:** {value}")?;
+            }
+            if let Some(description) = &herb.description {
+                writeln!(out, "\n{description}")?;
+            }
+        }
+        Format::Table => {
+            let mut table = Table::new();
+            table.set_header(["Herb", herb.name.as_str()]);
+            for (field, value) in fields {
+                table.add_row([field.to_string(), value]);
+            }
+            if let Some(description) = &herb.description {
+                table.add_row(["Description".to_string(), description.clone()]);
+            }
+            writeln!(out, "{table}")?;
+        }
+        Format::Json => {
+            #[derive(Serialize)]
+            struct JsonHerb<'a> {
+                name: &'a str,
+                rarity: &'a Rarity,
+                biomes: &'a [Biome],
+                tags: &'a [String],
+                /// Weight of one unit in pounds, or null.
+                weight: Option<f32>,
+                effect: Option<&'a str>,
+                description: Option<&'a str>,
+                source: Option<&'a Source>,
+            }
+            let json = JsonHerb {
+                name: &herb.name,
+                rarity: &herb.rarity,
+                biomes: &herb.biomes,
+                tags: &herb.tags,
+                weight: herb.weight,
+                effect: herb.effect.as_deref(),
+                description: herb.description.as_deref(),
+                source: herb.source.as_ref(),
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&json)?)?;
+        }
+        Format::Csv | Format::Tsv => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(if format == Format::Csv { b',' } else { b'\t' })
+                .quote_style(csv::QuoteStyle::NonNumeric)
+                .from_writer(out);
+            writer.write_record([
+                "Herb",
+                "Rarity",
+                "Biomes",
+                "Tags",
+                "Weight",
+                "Effect",
+                "Description",
+                "Source",
+            ])?;
+            writer.write_record([
+                herb.name.as_str(),
+                herb.rarity.to_string().as_str(),
+                biomes.join("; ").as_str(),
+                herb.tags.join("; ").as_str(),
+                herb.weight
+                    .map(|weight| weight.to_string())
+                    .unwrap_or_default()
+                    .as_str(),
+                herb.effect.as_deref().unwrap_or_default(),
+                herb.description.as_deref().unwrap_or_default(),
+                herb.source
+                    .as_ref()
+                    .map(Source::to_string)
+                    .unwrap_or_default()
+                    .as_str(),
+            ])?;
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// How available a herb is in one town, or in one of its shops.
+#[derive(Serialize)]
+pub struct Availability<'a> {
+    pub town: &'a str,
+    /// Merchant running the shop, or null for a town without `merchants`.
+    pub merchant: Option<&'a str>,
+    /// Rarity steps added because none of the herb's biomes is local to the town.
+    pub rarity_increase: u8,
+    /// Rarity the herb is stocked at in the town, or null when it can't be stocked there.
+    pub effective_rarity: Option<Rarity>,
+    /// Chance the herb is in stock at all.
+    pub likelihood: f32,
+    /// Quantity in stock in the generated market, 0 when it isn't stocked.
+    pub quantity: u16,
+    /// Price per unit in the generated market, or null when it isn't stocked.
+    pub price: Option<u64>,
+    /// Event happening in the town, or null.
+    pub event: Option<&'a str>,
+}
+
+/// Writes how available `herb` is across towns and their shops to `out`.
+pub fn write_comparison(
+    out: &mut dyn Write,
+    format: Format,
+    seed: &Seed,
+    cfg: &Config,
+    herb: &str,
+    availability: &[Availability],
+) -> Result<(), Box<dyn Error>> {
+    let currency = &cfg.currency;
+    let rarity_text = |availability: &Availability| {
+        availability
+            .effective_rarity
+            .as_ref()
+            .map_or_else(|| "-".to_string(), Rarity::to_string)
+    };
+    match format {
+        Format::Markdown | Format::Table => {
+            let mut table = Table::new();
+            let events = availability
+                .iter()
+                .any(|availability| availability.event.is_some());
+            let shops = availability
+                .iter()
+                .any(|availability| availability.merchant.is_some());
+            let mut header = vec!["Town"];
+            if shops {
+                header.push("Shop");
+            }
+            header.extend(["Rarity", "Chance", "Quantity", "Price"]);
+            if events {
+                header.push("Event");
+            }
+            table.set_header(header);
+            for availability in availability {
+                let mut row = vec![availability.town.to_string()];
+                if shops {
+                    row.push(availability.merchant.unwrap_or("-").to_string());
+                }
+                row.extend([
+                    rarity_text(availability),
+                    format!("{:.0}%", availability.likelihood * 100.0),
+                    availability.quantity.to_string(),
+                    availability
+                        .price
+                        .map_or_else(|| "-".to_string(), |price| currency.format(price)),
+                ]);
+                if events {
+                    row.push(availability.event.unwrap_or("-").to_string());
+                }
+                table.add_row(row);
+            }
+            writeln!(out, "Seed: {seed}")?;
+            writeln!(out, "Herb: {herb}")?;
+            if format == Format::Markdown {
+                writeln!(out, "```\n{table}\n```")?;
+            } else {
+                writeln!(out, "{table}")?;
+            }
+        }
+        Format::Json => {
+            #[derive(Serialize)]
+            struct JsonComparison<'a> {
+                seed: String,
+                herb: &'a str,
+                currency_unit: &'a str,
+                towns: &'a [Availability<'a>],
+            }
+            let comparison = JsonComparison {
+                seed: seed.to_string(),
+                herb,
+                currency_unit: currency
+                    .smallest()
+                    .map_or("", |denomination| denomination.name.as_str()),
+                towns: availability,
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&comparison)?)?;
+        }
+        Format::Csv | Format::Tsv => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(if format == Format::Csv { b',' } else { b'\t' })
+                .quote_style(csv::QuoteStyle::NonNumeric)
+                .from_writer(out);
+            let price_unit = currency.unit(None).ok_or("currency has no price_unit")?;
+            writer.write_record([
+                "Seed".to_string(),
+                "Herb".to_string(),
+                "Town".to_string(),
+                "Shop".to_string(),
+                "Rarity Increase".to_string(),
+                "Effective Rarity".to_string(),
+                "Likelihood".to_string(),
+                "Quantity".to_string(),
+                format!("Price ({})", price_unit.name),
+            ])?;
+            let seed = seed.to_string();
+            for availability in availability {
+                writer.write_record([
+                    seed.as_str(),
+                    herb,
+                    availability.town,
+                    availability.merchant.unwrap_or_default(),
+                    availability.rarity_increase.to_string().as_str(),
+                    availability
+                        .effective_rarity
+                        .as_ref()
+                        .map(Rarity::to_string)
+                        .unwrap_or_default()
+                        .as_str(),
+                    availability.likelihood.to_string().as_str(),
+                    availability.quantity.to_string().as_str(),
+                    availability
+                        .price
+                        .map(|price| currency.in_denomination(price, price_unit).to_string())
+                        .unwrap_or_default()
+                        .as_str(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `entries` of a market's ledger to `out` as a receipt, with what customers paid and
+/// received in total.
+pub fn write_ledger(
+    out: &mut dyn Write,
+    format: Format,
+    market: &str,
+    cfg: &Config,
+    entries: &[&LedgerEntry],
+) -> Result<(), Box<dyn Error>> {
+    let currency = &cfg.currency;
+    let total = |trade| -> u64 {
+        entries
+            .iter()
+            .filter(|entry| entry.trade == trade)
+            .map(|entry| entry.total())
+            .sum()
+    };
+    let (paid, received) = (total(Trade::Buy), total(Trade::Sell));
+    match format {
+        Format::Markdown | Format::Table => {
+            let mut table = Table::new();
+            table.set_header([
+                "Time", "Day", "Customer", "Trade", "Herb", "Quantity", "Each", "Total",
+            ]);
+            for entry in entries {
+                table.add_row([
+                    entry.time.format("%Y-%m-%d %H:%M").to_string(),
+                    entry.day.to_string(),
+                    entry.customer.clone(),
+                    trade_text(entry.trade).to_string(),
+                    entry.herb.clone(),
+                    entry.quantity.to_string(),
+                    currency.format(entry.price),
+                    currency.format(entry.total()),
+                ]);
+            }
+            let fence = format == Format::Markdown;
+            writeln!(out, "Market: {market}")?;
+            if fence {
+                writeln!(out, "```")?;
+            }
+            writeln!(out, "{table}")?;
+            if fence {
+                writeln!(out, "```")?;
+            }
+            writeln!(out, "Paid: {}", currency.format(paid))?;
+            writeln!(out, "Received: {}", currency.format(received))?;
+        }
+        Format::Json => {
+            #[derive(Serialize)]
+            struct JsonLedger<'a> {
+                market: &'a str,
+                currency_unit: &'a str,
+                /// Paid by customers for what they bought.
+                paid: u64,
+                /// Received by customers for what they sold.
+                received: u64,
+                entries: &'a [&'a LedgerEntry],
+            }
+            let ledger = JsonLedger {
+                market,
+                currency_unit: currency
+                    .smallest()
+                    .map_or("", |denomination| denomination.name.as_str()),
+                paid,
+                received,
+                entries,
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&ledger)?)?;
+        }
+        Format::Csv | Format::Tsv => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(if format == Format::Csv { b',' } else { b'\t' })
+                .quote_style(csv::QuoteStyle::NonNumeric)
+                .from_writer(out);
+            let price_unit = currency.unit(None).ok_or("currency has no price_unit")?;
+            writer.write_record([
+                "Market".to_string(),
+                "Time".to_string(),
+                "Day".to_string(),
+                "Customer".to_string(),
+                "Trade".to_string(),
+                "Herb".to_string(),
+                "Quantity".to_string(),
+                format!("Each ({})", price_unit.name),
+                format!("Total ({})", price_unit.name),
+            ])?;
+            for entry in entries {
+                writer.write_record([
+                    market,
+                    entry.time.to_rfc3339().as_str(),
+                    entry.day.to_string().as_str(),
+                    entry.customer.as_str(),
+                    trade_text(entry.trade),
+                    entry.herb.as_str(),
+                    entry.quantity.to_string().as_str(),
+                    currency
+                        .in_denomination(entry.price, price_unit)
+                        .to_string()
+                        .as_str(),
+                    currency
+                        .in_denomination(entry.total(), price_unit)
+                        .to_string()
+                        .as_str(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the herbs gathered by foraging `biomes` to `out`, along with every hour of the search
+/// when `verbose`.
+pub fn write_forage(
+    out: &mut dyn Write,
+    format: Format,
+    seed: &Seed,
+    cfg: &Config,
+    biomes: &[Biome],
+    searches: &[Search],
+    verbose: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut found: Vec<(&Herb, u16)> = Vec::new();
+    for search in searches.iter().filter(|search| search.quantity > 0) {
+        match found
+            .iter_mut()
+            .find(|(herb, _)| herb.name == search.herb.name)
+        {
+            Some((_, quantity)) => *quantity = quantity.saturating_add(search.quantity),
+            None => found.push((search.herb, search.quantity)),
+        }
+    }
+    found.sort_by_key(|(herb, _)| herb.name.clone());
+    let biome_names: Vec<&str> = biomes
+        .iter()
+        .map(|biome| cfg.biomes.display_name(biome))
+        .collect();
+    match format {
+        Format::Markdown | Format::Table => {
+            let fence = format == Format::Markdown;
+            let write_table = |out: &mut dyn Write, table: Table| -> std::io::Result<()> {
+                match fence {
+                    true => writeln!(out, "```\n{table}\n```"),
+                    false => writeln!(out, "{table}"),
+                }
+            };
+            writeln!(out, "Seed: {seed}")?;
+            writeln!(out, "Biomes: {}", biome_names.join(", "))?;
+            if let Some(season) = &cfg.season {
+                writeln!(out, "Season: {season}")?;
+            }
+            writeln!(out, "Hours: {}", searches.len())?;
+            if verbose {
+                let mut table = Table::new();
+                table.set_header(["Hour", "Herb", "DC", "Check", "Gathered"]);
+                for search in searches {
+                    let rolls: Vec<String> = [
+                        search
+                            .check_roll
+                            .as_ref()
+                            .map(|roll| format!("Check: {roll}")),
+                        search
+                            .quantity_roll
+                            .as_ref()
+                            .map(|roll| format!("Quantity: {roll}")),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                    table.add_row([
+                        search.hour.to_string(),
+                        search.herb.name.clone(),
+                        search.dc.to_string(),
+                        match rolls.is_empty() {
+                            true => search.check.to_string(),
+                            false => rolls.join("\n"),
+                        },
+                        search.quantity.to_string(),
+                    ]);
+                }
+                write_table(out, table)?;
+            }
+            if found.is_empty() {
+                writeln!(out, "Nothing found.")?;
+            } else {
+                let mut table = Table::new();
+                table.set_header(["Herb", "Rarity", "Quantity"]);
+                for (herb, quantity) in found.iter() {
+                    table.add_row([
+                        herb.name.clone(),
+                        herb.rarity.to_string(),
+                        quantity.to_string(),
+                    ]);
+                }
+                write_table(out, table)?;
+            }
+        }
+        Format::Json => {
+            #[derive(Serialize)]
+            struct JsonForage<'a> {
+                seed: String,
+                biomes: &'a [&'a str],
+                season: Option<&'a str>,
+                /// Herbs gathered over every hour, sorted by name.
+                found: Vec<JsonFound<'a>>,
+                searches: Vec<JsonSearch<'a>>,
+            }
+            #[derive(Serialize)]
+            struct JsonFound<'a> {
+                name: &'a str,
+                rarity: &'a Rarity,
+                quantity: u16,
+            }
+            #[derive(Serialize)]
+            struct JsonSearch<'a> {
+                hour: u32,
+                /// Herb the forager came across.
+                herb: &'a str,
+                dc: i64,
+                check: i64,
+                check_roll: Option<&'a str>,
+                /// Units gathered, 0 when the check failed.
+                quantity: u16,
+                quantity_roll: Option<&'a str>,
+            }
+            let forage = JsonForage {
+                seed: seed.to_string(),
+                biomes: &biome_names,
+                season: cfg.season.as_deref(),
+                found: found
+                    .iter()
+                    .map(|(herb, quantity)| JsonFound {
+                        name: &herb.name,
+                        rarity: &herb.rarity,
+                        quantity: *quantity,
+                    })
+                    .collect(),
+                searches: searches
+                    .iter()
+                    .map(|search| JsonSearch {
+                        hour: search.hour,
+                        herb: &search.herb.name,
+                        dc: search.dc,
+                        check: search.check,
+                        check_roll: search.check_roll.as_deref(),
+                        quantity: search.quantity,
+                        quantity_roll: search.quantity_roll.as_deref(),
+                    })
+                    .collect(),
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&forage)?)?;
+        }
+        Format::Csv | Format::Tsv => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(if format == Format::Csv { b',' } else { b'\t' })
+                .quote_style(csv::QuoteStyle::NonNumeric)
+                .from_writer(out);
+            writer.write_record(["Seed", "Herb", "Rarity", "Quantity"])?;
+            let seed = seed.to_string();
+            for (herb, quantity) in found.iter() {
+                writer.write_record([
+                    seed.as_str(),
+                    herb.name.as_str(),
+                    herb.rarity.to_string().as_str(),
+                    quantity.to_string().as_str(),
+                ])?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes which recipes of `plans` can be crafted, what they cost and the shopping list for each
+/// to `out`.
+pub fn write_crafting(
+    out: &mut dyn Write,
+    format: Format,
+    seed: &Seed,
+    cfg: &Config,
+    plans: &[Plan],
+    header: bool,
+) -> Result<(), Box<dyn Error>> {
+    let currency = &cfg.currency;
+    let missing_text = |plan: &Plan| {
+        let missing: Vec<String> = plan
+            .missing
+            .iter()
+            .map(|(herb, quantity)| format!("{quantity} {herb}"))
+            .collect();
+        format!("missing {}", missing.join(", "))
+    };
+    match format {
+        Format::Markdown | Format::Table => {
+            let fence = format == Format::Markdown;
+            let write_table = |out: &mut dyn Write, table: Table| -> std::io::Result<()> {
+                match fence {
+                    true => writeln!(out, "```\n{table}\n```"),
+                    false => writeln!(out, "{table}"),
+                }
+            };
+            let mut table = Table::new();
+            table.set_header(["Recipe", "Output", "DC", "Time", "Craftable At", "Cost"]);
+            for plan in plans {
+                let recipe = plan.recipe;
+                table.add_row([
+                    recipe.name.clone(),
+                    recipe.output.clone(),
+                    recipe
+                        .dc
+                        .map_or_else(|| "-".to_string(), |dc| dc.to_string()),
+                    recipe.craft_time.clone().unwrap_or_else(|| "-".to_string()),
+                    match plan.craftable_at.is_empty() {
+                        true => "-".to_string(),
+                        false => plan.craftable_at.join("\n"),
+                    },
+                    match plan.complete() {
+                        true => currency.format(plan.cost()),
+                        false => missing_text(plan),
+                    },
+                ]);
+            }
+            writeln!(out, "Seed: {seed}")?;
+            write_table(out, table)?;
+            for plan in plans.iter().filter(|plan| !plan.shopping_list.is_empty()) {
+                let mut table = Table::new();
+                table.set_header(["Herb", "Market", "Quantity", "Each", "Total"]);
+                for purchase in plan.shopping_list.iter() {
+                    table.add_row([
+                        purchase.herb.to_string(),
+                        purchase.market.to_string(),
+                        purchase.quantity.to_string(),
+                        currency.format(purchase.price),
+                        currency.format(purchase.total()),
+                    ]);
+                }
+                writeln!(out, "Shopping list for {}:", plan.recipe.name)?;
+                write_table(out, table)?;
+            }
+        }
+        Format::Json => {
+            #[derive(Serialize)]
+            struct JsonCrafting<'a> {
+                seed: String,
+                currency_unit: &'a str,
+                recipes: Vec<JsonPlan<'a>>,
+            }
+            #[derive(Serialize)]
+            struct JsonPlan<'a> {
+                name: &'a str,
+                output: &'a str,
+                dc: Option<i64>,
+                craft_time: Option<&'a str>,
+                /// Markets stocking every ingredient.
+                craftable_at: &'a [&'a str],
+                /// Cost of the shopping list, or null when ingredients are missing.
+                cost: Option<u64>,
+                shopping_list: Vec<JsonPurchase<'a>>,
+                missing: Vec<JsonMissing<'a>>,
+            }
+            #[derive(Serialize)]
+            struct JsonPurchase<'a> {
+                herb: &'a str,
+                market: &'a str,
+                quantity: u16,
+                price: u64,
+                total: u64,
+            }
+            #[derive(Serialize)]
+            struct JsonMissing<'a> {
+                herb: &'a str,
+                quantity: u16,
+            }
+            let crafting = JsonCrafting {
+                seed: seed.to_string(),
+                currency_unit: currency
+                    .smallest()
+                    .map_or("", |denomination| denomination.name.as_str()),
+                recipes: plans
+                    .iter()
+                    .map(|plan| JsonPlan {
+                        name: &plan.recipe.name,
+                        output: &plan.recipe.output,
+                        dc: plan.recipe.dc,
+                        craft_time: plan.recipe.craft_time.as_deref(),
+                        craftable_at: &plan.craftable_at,
+                        cost: plan.complete().then(|| plan.cost()),
+                        shopping_list: plan
+                            .shopping_list
+                            .iter()
+                            .map(|purchase| JsonPurchase {
+                                herb: purchase.herb,
+                                market: purchase.market,
+                                quantity: purchase.quantity,
+                                price: purchase.price,
+                                total: purchase.total(),
+                            })
+                            .collect(),
+                        missing: plan
+                            .missing
+                            .iter()
+                            .map(|&(herb, quantity)| JsonMissing { herb, quantity })
+                            .collect(),
+                    })
+                    .collect(),
+            };
+            writeln!(out, "{}", serde_json::to_string_pretty(&crafting)?)?;
+        }
+        Format::Csv | Format::Tsv => {
+            let mut writer = csv::WriterBuilder::new()
+                .delimiter(if format == Format::Csv { b',' } else { b'\t' })
+                .quote_style(csv::QuoteStyle::NonNumeric)
+                .from_writer(out);
+            let price_unit = currency.unit(None).ok_or("currency has no price_unit")?;
+            if header {
+                writer.write_record([
+                    "Seed".to_string(),
+                    "Recipe".to_string(),
+                    "Herb".to_string(),
+                    "Market".to_string(),
+                    "Quantity".to_string(),
+                    format!("Each ({})", price_unit.name),
+                    format!("Total ({})", price_unit.name),
+                ])?;
+            }
+            let seed = seed.to_string();
+            let in_unit = |amount: u64| currency.in_denomination(amount, price_unit).to_string();
+            for plan in plans {
+                for purchase in plan.shopping_list.iter() {
+                    writer.write_record([
+                        seed.as_str(),
+                        plan.recipe.name.as_str(),
+                        purchase.herb,
+                        purchase.market,
+                        purchase.quantity.to_string().as_str(),
+                        in_unit(purchase.price).as_str(),
+                        in_unit(purchase.total()).as_str(),
+                    ])?;
+                }
+                // Missing herbs have no market to buy them from, or price.
+                for (herb, quantity) in plan.missing.iter() {
+                    writer.write_record([
+                        seed.as_str(),
+                        plan.recipe.name.as_str(),
+                        herb,
+                        "",
+                        quantity.to_string().as_str(),
+                        "",
+                        "",
+                    ])?;
+                }
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+fn trade_text(trade: Trade) -> &'static str {
+    match trade {
+        Trade::Buy => "Bought",
+        Trade::Sell => "Sold",
+    }
+}
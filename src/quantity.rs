@@ -0,0 +1,87 @@
+use crate::dice::Dice;
+use rand::Rng;
+use serde::Deserialize;
+
+/// How many units of a herb are on the shelf once it is in stock.
+///
+/// Draws below 1 count as 1, since whether a herb is stocked at all is decided by the
+/// tier's `likelihood`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Default)]
+#[serde(tag = "distribution", rename_all = "lowercase")]
+pub enum Quantity {
+    /// One more unit for as long as rolls keep landing under `likelihood`.
+    #[default]
+    Geometric,
+    /// Number of successes out of `max` rolls at `chance`.
+    Binomial { max: u16, chance: f32 },
+    /// Poisson distributed around `mean`.
+    Poisson { mean: f32 },
+    /// Uniform between `min` and `max`, inclusive.
+    Range { min: u16, max: u16 },
+    /// Result of rolling `dice`, e.g. `"2d4-1"` or `"4d4kl2"`.
+    Dice { dice: Dice },
+}
+
+impl Quantity {
+    /// Draws a quantity, along with the dice breakdown when it came from a dice expression.
+    pub fn sample<R: Rng>(&self, likelihood: f32, rng: &mut R) -> (u16, Option<String>) {
+        let quantity = match self {
+            Quantity::Geometric => {
+                let mut quantity = 1;
+                while rng.gen_range(0.0f32..1.0f32) < likelihood {
+                    quantity += 1;
+                }
+                quantity
+            }
+            Quantity::Binomial { max, chance } => (0..*max)
+                .filter(|_| rng.gen_range(0.0f32..1.0f32) < *chance)
+                .count() as u16,
+            Quantity::Poisson { mean } => poisson(*mean, rng),
+            Quantity::Range { min, max } => rng.gen_range(*min..=*max),
+            Quantity::Dice { dice } => {
+                let roll = dice.roll(rng);
+                let quantity = roll.total.clamp(1, i64::from(u16::MAX)) as u16;
+                return (quantity, Some(roll.breakdown));
+            }
+        };
+        (quantity.max(1), None)
+    }
+
+    /// Problems with the parameters, if any.
+    pub fn check(&self) -> Option<String> {
+        match self {
+            Quantity::Binomial { chance, .. } if !(0.0..=1.0).contains(chance) => Some(format!(
+                "binomial chance is {chance}, it must be between 0 and 1"
+            )),
+            Quantity::Poisson { mean } if !(mean.is_finite() && *mean >= 0.0) => {
+                Some(format!("poisson mean is {mean}, it must be at least 0"))
+            }
+            Quantity::Range { min, max } if min > max => {
+                Some(format!("range min ({min}) is greater than max ({max})"))
+            }
+            Quantity::Dice { dice } if dice.max() < 1 => {
+                Some(format!("dice {dice} can only roll below 1"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Knuth's method, which is plenty for the small means shelf stock has.
+fn poisson<R: Rng>(mean: f32, rng: &mut R) -> u16 {
+    let limit = (-f64::from(mean)).exp();
+    let mut product = rng.gen_range(0.0f64..1.0f64);
+    let mut count = 0u16;
+    while product > limit && count < u16::MAX {
+        count += 1;
+        product *= rng.gen_range(0.0f64..1.0f64);
+    }
+    count
+}
+
+/// `quantity` scaled by `multiplier`, rounded but never below 1.
+pub fn scale_quantity(quantity: u16, multiplier: f32) -> u16 {
+    (f32::from(quantity) * multiplier)
+        .round()
+        .clamp(1.0, f32::from(u16::MAX)) as u16
+}
@@ -0,0 +1,52 @@
+use rand::{thread_rng, Rng};
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::fmt::{self, Display, Formatter};
+
+/// Seed of a generated market, either a number or free text such as `Waterdeep-session-12`.
+///
+/// The same seed always produces the same market: numbers are used as is, text is run through
+/// FNV-1a, and the stream comes from ChaCha8, whose output is fixed by its spec. How values are
+/// sampled from the stream, e.g. by `gen_range`, is up to `rand` and may change between its
+/// major versions, so markets stay reproducible only while `rand` is kept at 0.8, which
+/// `seeds_produce_pinned_stock` checks.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Seed(String);
+
+impl Seed {
+    pub fn random() -> Self {
+        Seed(thread_rng().gen::<u64>().to_string())
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0.parse().unwrap_or_else(|_| fnv1a(self.0.as_bytes()))
+    }
+
+    pub fn rng(&self) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(self.value())
+    }
+
+    /// Seed for one part of what this seed generates, e.g. a single town or day, so that parts
+    /// don't share a stream.
+    pub fn derive(&self, part: &str) -> Seed {
+        Seed(format!("{}/{part}", self.0))
+    }
+}
+
+impl From<String> for Seed {
+    fn from(s: String) -> Self {
+        Seed(s)
+    }
+}
+
+impl Display for Seed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,470 @@
+use crate::config::{Biome, Config, Demand, Distance, MerchantConfig, Rarity};
+use crate::craft::Purchase;
+use crate::seed::Seed;
+use crate::stock::{herb_stocking, max_quantity, stock_herb, HerbStock};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+/// A market saved between sessions, so players find the same shelves when they come back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketState {
+    pub name: String,
+    /// Seed the market was generated from. Each day gets its own seed derived from it.
+    pub seed: String,
+    /// In-game days since the market was generated.
+    pub day: u32,
+    /// Day any herb was last restocked.
+    pub last_restock: u32,
+    pub local_biomes: HashSet<Biome>,
+    pub biome_distances: HashMap<Biome, Distance>,
+    pub not_local_rarity_increase: u8,
+    /// Missing from markets saved before towns could override it, which used the config's.
+    #[serde(default)]
+    pub nearby_rarity_increase: Option<u8>,
+    #[serde(default)]
+    pub size: Option<String>,
+    /// Merchant running the shop, or `None` for a whole market.
+    #[serde(default)]
+    pub merchant: Option<String>,
+    #[serde(default)]
+    pub season: Option<String>,
+    #[serde(default)]
+    pub event: Option<String>,
+    pub stock: Vec<StockEntry>,
+    /// Every purchase and sale, oldest first. Entries are only ever added.
+    #[serde(default)]
+    pub ledger: Vec<LedgerEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockEntry {
+    pub herb: String,
+    pub effective_rarity: Rarity,
+    pub rarity_increase: u8,
+    pub quantity: u16,
+    /// Price per unit in the smallest denomination of the config's currency.
+    pub price: u64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trade {
+    /// A customer bought from the market.
+    Buy,
+    /// A customer sold to the market.
+    Sell,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// When the trade was recorded.
+    pub time: DateTime<Utc>,
+    /// In-game day of the market the trade happened on.
+    pub day: u32,
+    pub customer: String,
+    pub trade: Trade,
+    pub herb: String,
+    pub quantity: u16,
+    /// Price per unit paid, after the spread.
+    pub price: u64,
+}
+
+impl LedgerEntry {
+    /// Price of all units together.
+    pub fn total(&self) -> u64 {
+        self.price * u64::from(self.quantity)
+    }
+}
+
+impl MarketState {
+    /// State of a freshly generated market. `cfg` is the config it was generated from, with any
+    /// locality overrides applied.
+    pub fn new(
+        name: String,
+        seed: &Seed,
+        cfg: &Config,
+        merchant: Option<&MerchantConfig>,
+        stock: &[HerbStock],
+    ) -> Self {
+        MarketState {
+            name,
+            seed: seed.to_string(),
+            day: 0,
+            last_restock: 0,
+            local_biomes: cfg.local_biomes.clone(),
+            biome_distances: cfg.biome_distances.clone(),
+            not_local_rarity_increase: cfg.not_local_rarity_increase,
+            nearby_rarity_increase: Some(cfg.nearby_rarity_increase),
+            size: cfg.size.clone(),
+            merchant: merchant.map(|merchant| merchant.name.clone()),
+            season: cfg.season.clone(),
+            event: cfg.event.clone(),
+            stock: stock.iter().map(StockEntry::from).collect(),
+            ledger: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text =
+            read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        Ok(serde_json::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        write(path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        Ok(())
+    }
+
+    /// Replaces the locality, size, season and event of `cfg` with those of the market.
+    pub fn apply_locality(&self, cfg: &mut Config) {
+        cfg.local_biomes = self.local_biomes.clone();
+        cfg.biome_distances = self.biome_distances.clone();
+        cfg.not_local_rarity_increase = self.not_local_rarity_increase;
+        if let Some(nearby_rarity_increase) = self.nearby_rarity_increase {
+            cfg.nearby_rarity_increase = nearby_rarity_increase;
+        }
+        cfg.size = self.size.clone();
+        cfg.season = self.season.clone();
+        cfg.event = self.event.clone();
+    }
+
+    /// Merchant running the shop, looked up in `cfg`.
+    pub fn merchant<'a>(&self, cfg: &'a Config) -> Result<Option<&'a MerchantConfig>, String> {
+        self.merchant
+            .as_deref()
+            .map(|name| {
+                cfg.merchant(name)
+                    .ok_or_else(|| format!("merchant {name:?} is not in the config"))
+            })
+            .transpose()
+    }
+
+    /// Herbs on the shelf, looked up in `cfg`.
+    pub fn stock(&self, cfg: &Config) -> Result<Vec<HerbStock>, String> {
+        self.stock
+            .iter()
+            .filter(|entry| entry.quantity > 0)
+            .map(|entry| {
+                let herb = cfg
+                    .herb(&entry.herb)
+                    .ok_or_else(|| format!("herb {:?} is not in the config", entry.herb))?;
+                Ok(HerbStock {
+                    herb: herb.clone(),
+                    effective_rarity: entry.effective_rarity.clone(),
+                    rarity_increase: entry.rarity_increase,
+                    quantity: entry.quantity,
+                    price: entry.price,
+                    quantity_roll: None,
+                    price_roll: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Moves the market `days` days forward. Every day prices revert toward their baseline by
+    /// their tier's `demand.reversion` and drift by up to `price_drift`, and each herb restocks
+    /// with a chance of one in its tier's `restock_days`, refilling its shelf up to a freshly
+    /// rolled quantity.
+    pub fn advance(&mut self, cfg: &Config, days: u32) -> Result<(), String> {
+        let merchant = self.merchant(cfg)?;
+        for _ in 0..days {
+            self.day += 1;
+            let mut rng = Seed::from(self.seed.clone())
+                .derive(&format!("day {}", self.day))
+                .rng();
+            for entry in self.stock.iter_mut() {
+                let drift = rng.gen_range(-cfg.price_drift..=cfg.price_drift);
+                let Some((baseline, demand)) = pricing(cfg, merchant, &entry.herb) else {
+                    continue;
+                };
+                let price = entry.price as f64;
+                let reverted = price + (baseline as f64 - price) * f64::from(demand.reversion);
+                entry.price = demand.clamp(reverted * f64::from(1.0 + drift), baseline);
+            }
+            for herb in cfg.herbs.iter() {
+                let Some(stocking) = herb_stocking(cfg, merchant, herb) else {
+                    continue;
+                };
+                if rng.gen_range(0.0f32..1.0f32) >= 1.0 / stocking.rarity_config.restock_days {
+                    continue;
+                }
+                let Some(restocked) = stock_herb(cfg, merchant, herb, &mut rng) else {
+                    continue;
+                };
+                self.last_restock = self.day;
+                match self.stock.iter_mut().find(|entry| entry.herb == herb.name) {
+                    Some(entry) => {
+                        entry.quantity = entry.quantity.max(restocked.quantity);
+                        if let Some(max_quantity) = max_quantity(cfg, merchant, herb) {
+                            entry.quantity = entry.quantity.min(max_quantity);
+                        }
+                    }
+                    None => self.stock.push(StockEntry::from(&restocked)),
+                }
+            }
+        }
+        self.stock.sort_by(|a, b| a.herb.cmp(&b.herb));
+        Ok(())
+    }
+
+    /// Sells `quantity` of `herb` to `customer` at the current price plus `buy_spread`, then
+    /// raises the price by its tier's `demand.purchase_impact` for every unit.
+    pub fn buy(
+        &mut self,
+        cfg: &Config,
+        herb: &str,
+        quantity: u16,
+        customer: &str,
+    ) -> Result<&LedgerEntry, String> {
+        let herb = cfg
+            .herb(herb)
+            .ok_or_else(|| format!("unknown herb {herb:?}"))?;
+        let merchant = self.merchant(cfg)?;
+        let entry = self
+            .stock
+            .iter_mut()
+            .find(|entry| entry.herb == herb.name)
+            .filter(|entry| entry.quantity > 0)
+            .ok_or_else(|| format!("{} is out of stock", herb.name))?;
+        if entry.quantity < quantity {
+            return Err(format!("only {} {} in stock", entry.quantity, herb.name));
+        }
+        entry.quantity -= quantity;
+        let price = spread(entry.price, 1.0 + cfg.buy_spread);
+        if let Some((baseline, demand)) = pricing(cfg, merchant, &herb.name) {
+            let impact = f64::from(1.0 + demand.purchase_impact).powi(i32::from(quantity));
+            entry.price = demand.clamp(entry.price as f64 * impact, baseline);
+        }
+        Ok(self.record(Trade::Buy, &herb.name, quantity, price, customer))
+    }
+
+    /// Prices `purchases` at what `buy` would charge for them, made one after the other, so that
+    /// `buy_spread` and `demand.purchase_impact` are included. The market itself is unchanged.
+    pub fn quote(&self, cfg: &Config, purchases: &mut [Purchase]) -> Result<(), String> {
+        let mut market = self.clone();
+        for purchase in purchases.iter_mut() {
+            purchase.price = market.buy(cfg, purchase.herb, purchase.quantity, "")?.price;
+        }
+        Ok(())
+    }
+
+    /// Buys `quantity` of `herb` from `customer` at the current price minus `sell_spread`, then
+    /// lowers the price by its tier's `demand.sale_impact` for every unit. Herbs the market has
+    /// never stocked are priced at their baseline.
+    pub fn sell(
+        &mut self,
+        cfg: &Config,
+        herb: &str,
+        quantity: u16,
+        customer: &str,
+    ) -> Result<&LedgerEntry, String> {
+        let herb = cfg
+            .herb(herb)
+            .ok_or_else(|| format!("unknown herb {herb:?}"))?;
+        let merchant = self.merchant(cfg)?;
+        let (baseline, demand) = pricing(cfg, merchant, &herb.name)
+            .ok_or_else(|| format!("the market doesn't trade in {}", herb.name))?;
+        if !self.stock.iter().any(|entry| entry.herb == herb.name) {
+            let stocking = herb_stocking(cfg, merchant, herb)
+                .ok_or_else(|| format!("the market doesn't trade in {}", herb.name))?;
+            self.stock.push(StockEntry {
+                herb: herb.name.clone(),
+                effective_rarity: stocking.rarity_config.name,
+                rarity_increase: cfg.rarity_increase(herb),
+                quantity: 0,
+                price: baseline,
+            });
+            self.stock.sort_by(|a, b| a.herb.cmp(&b.herb));
+        }
+        let entry = self
+            .stock
+            .iter_mut()
+            .find(|entry| entry.herb == herb.name)
+            .expect("herb was just stocked");
+        entry.quantity = entry.quantity.saturating_add(quantity);
+        let price = spread(entry.price, 1.0 - cfg.sell_spread);
+        let impact = f64::from(1.0 - demand.sale_impact).powi(i32::from(quantity));
+        entry.price = demand.clamp(entry.price as f64 * impact, baseline);
+        Ok(self.record(Trade::Sell, &herb.name, quantity, price, customer))
+    }
+
+    fn record(
+        &mut self,
+        trade: Trade,
+        herb: &str,
+        quantity: u16,
+        price: u64,
+        customer: &str,
+    ) -> &LedgerEntry {
+        self.ledger.push(LedgerEntry {
+            time: Utc::now(),
+            day: self.day,
+            customer: customer.to_string(),
+            trade,
+            herb: herb.to_string(),
+            quantity,
+            price,
+        });
+        &self.ledger[self.ledger.len() - 1]
+    }
+}
+
+/// Baseline price of the herb called `name`, which is the average price of its tier scaled by
+/// its multiplier, along with how its price reacts to trade. `None` when the herb can't be
+/// stocked.
+fn pricing(cfg: &Config, merchant: Option<&MerchantConfig>, name: &str) -> Option<(u64, Demand)> {
+    let stocking = herb_stocking(cfg, merchant, cfg.herb(name)?)?;
+    let baseline =
+        stocking.rarity_config.price.average(&cfg.currency) * f64::from(stocking.price_multiplier);
+    Some((baseline.round() as u64, stocking.rarity_config.demand))
+}
+
+fn spread(price: u64, factor: f32) -> u64 {
+    (price as f64 * f64::from(factor)).round() as u64
+}
+
+impl From<&HerbStock> for StockEntry {
+    fn from(herb_stock: &HerbStock) -> Self {
+        StockEntry {
+            herb: herb_stock.herb.name.clone(),
+            effective_rarity: herb_stock.effective_rarity.clone(),
+            rarity_increase: herb_stock.rarity_increase,
+            quantity: herb_stock.quantity,
+            price: herb_stock.price,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stock::generate_stock;
+
+    const CONFIG: &str = r#"
+        local_biomes = ["Forest"]
+        not_local_rarity_increase = 1
+        price_drift = 0.0
+        buy_spread = 0.1
+        sell_spread = 0.5
+
+        [[rarities]]
+        name = "Common"
+        price_lower = 10
+        price_upper = 10
+        likelihood = 0.99
+        restock_days = 1
+        quantity = { distribution = "range", min = 1, max = 4 }
+        demand = { purchase_impact = 0.1, sale_impact = 0.1, reversion = 0.5, max_multiplier = 1.5 }
+
+        [[herbs]]
+        name = "Arrow Root"
+        rarity = "Common"
+        biomes = ["Forest"]
+
+        [[herbs]]
+        name = "Elven Ivy"
+        rarity = "Common"
+        biomes = ["Forest"]
+    "#;
+
+    fn market() -> (Config, MarketState) {
+        let cfg = toml::from_str::<Config>(CONFIG)
+            .unwrap()
+            .resolved()
+            .unwrap();
+        let seed = Seed::from("42".to_string());
+        let stock = generate_stock(&cfg, None, &mut seed.rng());
+        let state = MarketState::new("Test".to_string(), &seed, &cfg, None, &stock);
+        (cfg, state)
+    }
+
+    fn entry<'a>(state: &'a MarketState, herb: &str) -> &'a StockEntry {
+        state.stock.iter().find(|entry| entry.herb == herb).unwrap()
+    }
+
+    #[test]
+    fn buy_charges_spread_and_takes_stock() {
+        let (cfg, mut state) = market();
+        let before = entry(&state, "Arrow Root").quantity;
+        assert_eq!(entry(&state, "Arrow Root").price, 1000);
+        let bought = state.buy(&cfg, "arrow root", 1, "Vex").unwrap();
+        assert_eq!((bought.price, bought.quantity), (1100, 1));
+        assert_eq!(bought.customer, "Vex");
+        assert_eq!(entry(&state, "Arrow Root").quantity, before - 1);
+        assert_eq!(state.ledger.len(), 1);
+
+        let error = state.buy(&cfg, "Arrow Root", before, "Vex").unwrap_err();
+        assert!(error.contains("in stock"), "{error}");
+        assert_eq!(state.ledger.len(), 1);
+    }
+
+    #[test]
+    fn sell_pays_less_spread_and_adds_stock() {
+        let (cfg, mut state) = market();
+        let before = entry(&state, "Elven Ivy").quantity;
+        let sold = state.sell(&cfg, "Elven Ivy", 2, "Vex").unwrap();
+        assert_eq!((sold.price, sold.total()), (500, 1000));
+        assert_eq!(sold.trade, Trade::Sell);
+        assert_eq!(entry(&state, "Elven Ivy").quantity, before + 2);
+    }
+
+    #[test]
+    fn restocking_stays_within_fresh_quantities() {
+        let (cfg, mut state) = market();
+        state.advance(&cfg, 365).unwrap();
+        assert_eq!(state.stock.len(), 2);
+        for entry in state.stock.iter() {
+            assert!(
+                (1..=4).contains(&entry.quantity),
+                "{} has {} units after a year",
+                entry.herb,
+                entry.quantity
+            );
+        }
+    }
+
+    #[test]
+    fn buying_raises_price_up_to_max_multiplier() {
+        let (cfg, mut state) = market();
+        state.stock[0].quantity = 20;
+        state.buy(&cfg, "Arrow Root", 2, "Vex").unwrap();
+        assert_eq!(entry(&state, "Arrow Root").price, 1210);
+        let bought = state.buy(&cfg, "Arrow Root", 10, "Vex").unwrap();
+        assert_eq!(bought.price, 1331);
+        assert_eq!(entry(&state, "Arrow Root").price, 1500);
+    }
+
+    #[test]
+    fn selling_lowers_price_down_to_min_multiplier() {
+        let (cfg, mut state) = market();
+        state.sell(&cfg, "Elven Ivy", 2, "Vex").unwrap();
+        assert_eq!(entry(&state, "Elven Ivy").price, 810);
+        state.sell(&cfg, "Elven Ivy", 20, "Vex").unwrap();
+        assert_eq!(entry(&state, "Elven Ivy").price, 500);
+    }
+
+    #[test]
+    fn prices_revert_toward_baseline() {
+        let (cfg, mut state) = market();
+        state.stock[0].price = 1500;
+        state.stock[1].price = 600;
+        state.advance(&cfg, 1).unwrap();
+        assert_eq!(entry(&state, "Arrow Root").price, 1250);
+        assert_eq!(entry(&state, "Elven Ivy").price, 800);
+        state.advance(&cfg, 30).unwrap();
+        for entry in state.stock.iter() {
+            assert!(
+                entry.price.abs_diff(1000) <= 1,
+                "{} is {}",
+                entry.herb,
+                entry.price
+            );
+        }
+    }
+}
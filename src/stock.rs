@@ -0,0 +1,322 @@
+use crate::config::{
+    Config, EventConfig, Herb, MerchantConfig, Rarity, RarityConfig, RarityOverflow, StockScaling,
+};
+use crate::quantity::scale_quantity;
+use rand::Rng;
+
+pub struct HerbStock {
+    pub herb: Herb,
+    pub effective_rarity: Rarity,
+    pub rarity_increase: u8,
+    pub quantity: u16,
+    /// Price per unit in the smallest denomination of the config's currency.
+    pub price: u64,
+    /// Dice breakdown of the quantity, when it was rolled from a dice expression.
+    pub quantity_roll: Option<String>,
+    /// Dice breakdown of the price, when it was rolled from a dice expression.
+    pub price_roll: Option<String>,
+}
+
+/// Stock of a market, or of `merchant`'s shop when given.
+pub fn generate_stock<R: Rng>(
+    cfg: &Config,
+    merchant: Option<&MerchantConfig>,
+    rng: &mut R,
+) -> Vec<HerbStock> {
+    cfg.herbs
+        .iter()
+        .filter_map(|herb| stock_herb(cfg, merchant, herb, rng))
+        .collect()
+}
+
+/// Rolls whether `herb` is in stock and, if so, how many at what price.
+pub fn stock_herb<R: Rng>(
+    cfg: &Config,
+    merchant: Option<&MerchantConfig>,
+    herb: &Herb,
+    rng: &mut R,
+) -> Option<HerbStock> {
+    let Stocking {
+        rarity_config,
+        likelihood,
+        price_multiplier,
+    } = herb_stocking(cfg, merchant, herb)?;
+    let effective_rarity = rarity_config.name.clone();
+    if rng.gen_range(0.0f32..1.0f32) >= likelihood {
+        return None;
+    }
+    let (mut quantity, mut quantity_roll) =
+        rarity_config.quantity.sample(rarity_config.likelihood, rng);
+    let multiplier = quantity_multiplier(cfg, merchant, herb);
+    if multiplier != 1.0 {
+        let scaled = scale_quantity(quantity, multiplier);
+        quantity_roll = quantity_roll.map(|roll| format!("{roll} × {multiplier} = {scaled}"));
+        quantity = scaled;
+    }
+    if let Some(max_quantity) = max_quantity(cfg, merchant, herb) {
+        quantity = quantity.min(max_quantity);
+    }
+    let (price, price_roll) = roll_price(cfg, &rarity_config, price_multiplier, rng);
+    Some(HerbStock {
+        herb: herb.clone(),
+        effective_rarity,
+        rarity_increase: cfg.rarity_increase(herb),
+        quantity,
+        price,
+        quantity_roll,
+        price_roll,
+    })
+}
+
+/// Rolls the price of a herb stocked with `rarity_config`, scaled by `price_multiplier`.
+pub fn roll_price<R: Rng>(
+    cfg: &Config,
+    rarity_config: &RarityConfig,
+    price_multiplier: f32,
+    rng: &mut R,
+) -> (u64, Option<String>) {
+    let (mut price, mut price_roll) = rarity_config.price.roll(&cfg.currency, rng);
+    if price_multiplier != 1.0 {
+        let scaled = (price as f64 * f64::from(price_multiplier)).round() as u64;
+        price_roll = price_roll.map(|roll| {
+            let scaled = cfg.currency.format(scaled);
+            format!("{roll} × {price_multiplier} = {scaled}")
+        });
+        price = scaled;
+    }
+    (price, price_roll)
+}
+
+/// Rolls the event happening at a market, `None` when there is none.
+pub fn roll_event<'a, R: Rng>(cfg: &'a Config, rng: &mut R) -> Option<&'a EventConfig> {
+    if cfg.events.is_empty() || rng.gen_range(0.0f32..1.0f32) >= cfg.event_chance {
+        return None;
+    }
+    let total: f32 = cfg.events.iter().map(|event| event.weight).sum();
+    let mut roll = rng.gen_range(0.0f32..1.0f32) * total;
+    cfg.events
+        .iter()
+        .filter(|event| event.weight > 0.0)
+        .find(|event| {
+            roll -= event.weight;
+            roll < 0.0
+        })
+        .or_else(|| cfg.events.iter().rev().find(|event| event.weight > 0.0))
+}
+
+/// Most of `herb` the market stocks, after scaling by its size, `merchant` and event.
+pub fn max_quantity(cfg: &Config, merchant: Option<&MerchantConfig>, herb: &Herb) -> Option<u16> {
+    let max_quantity = herb.max_quantity?;
+    let multiplier = quantity_multiplier(cfg, merchant, herb);
+    Some(match multiplier != 1.0 {
+        true => scale_quantity(max_quantity, multiplier),
+        false => max_quantity,
+    })
+}
+
+fn quantity_multiplier(cfg: &Config, merchant: Option<&MerchantConfig>, herb: &Herb) -> f32 {
+    let multiplier: f32 = scalings(cfg, merchant)
+        .map(|scaling| scaling.quantity_multiplier)
+        .product();
+    match event(cfg, herb) {
+        Some(event) => multiplier * event.quantity_multiplier,
+        None => multiplier,
+    }
+}
+
+/// The market's event, if it affects `herb`.
+fn event<'a>(cfg: &'a Config, herb: &Herb) -> Option<&'a EventConfig> {
+    cfg.event_config().filter(|event| event.herbs.matches(herb))
+}
+
+/// Multipliers of the market's size and `merchant`, whichever there are.
+fn scalings<'a>(
+    cfg: &'a Config,
+    merchant: Option<&'a MerchantConfig>,
+) -> impl Iterator<Item = &'a StockScaling> {
+    cfg.size_config()
+        .map(|size| &size.scaling)
+        .into_iter()
+        .chain(merchant.map(|merchant| &merchant.scaling))
+}
+
+/// How a herb is stocked in a market.
+pub struct Stocking {
+    /// Settings of the herb's tier after the non-local increase, with the herb's own overrides.
+    /// Its `likelihood` drives the quantity distribution.
+    pub rarity_config: RarityConfig,
+    /// Chance the herb is in stock, after the season, the market's event, size and merchant.
+    pub likelihood: f32,
+    /// Multiplier for the herb's prices, including any transport markup.
+    pub price_multiplier: f32,
+}
+
+/// How `herb` is stocked in the market, or by `merchant`. `None` when the herb can't be stocked.
+pub fn herb_stocking(
+    cfg: &Config,
+    merchant: Option<&MerchantConfig>,
+    herb: &Herb,
+) -> Option<Stocking> {
+    if herb.never_in_stock || merchant.is_some_and(|merchant| !merchant.herbs.matches(herb)) {
+        return None;
+    }
+    let rarity_increase = cfg.rarity_increase(herb);
+    let mut effective_rarity = herb.rarity.clone();
+    let mut exotic = None;
+    for _ in 0..rarity_increase {
+        match effective_rarity.next_rarity(&cfg.rarities) {
+            Some(next_rarity) => effective_rarity = next_rarity,
+            None => match &cfg.rarity_overflow {
+                RarityOverflow::Drop => return None,
+                RarityOverflow::Cap => break,
+                RarityOverflow::Exotic(exotic_config) => {
+                    exotic = Some(exotic_config);
+                    break;
+                }
+            },
+        }
+    }
+    let mut rarity_config = cfg.rarities.config(&effective_rarity)?.clone();
+    let mut price_multiplier = herb.price_multiplier * (1.0 + cfg.herb_import(herb).markup);
+    if let Some(exotic_config) = exotic {
+        rarity_config = exotic_config.rarity_config(&rarity_config);
+        price_multiplier *= exotic_config.price_multiplier;
+    }
+    if let Some(price) = &herb.price.0 {
+        rarity_config.price = price.clone();
+    }
+    if let Some(likelihood) = herb.likelihood {
+        rarity_config.likelihood = likelihood;
+    }
+    let mut likelihood = rarity_config.likelihood;
+    if let Some(season) = &cfg.season {
+        let modifier = herb.in_season(season)?;
+        likelihood *= modifier.likelihood_multiplier;
+        price_multiplier *= modifier.price_multiplier;
+    }
+    if let Some(event) = event(cfg, herb) {
+        likelihood *= event.likelihood_multiplier;
+        price_multiplier *= event.price_multiplier;
+    }
+    for scaling in scalings(cfg, merchant) {
+        if let Some(multiplier) = scaling.likelihood.get(&rarity_config.name) {
+            likelihood *= multiplier;
+        }
+        price_multiplier *= scaling.price_multiplier;
+    }
+    Some(Stocking {
+        rarity_config,
+        likelihood: likelihood.min(1.0),
+        price_multiplier,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seed::Seed;
+
+    const CONFIG: &str = r#"
+        local_biomes = ["Forest"]
+        not_local_rarity_increase = 1
+
+        [[rarities]]
+        name = "Common"
+        price_lower = 3
+        price_upper = 20
+        likelihood = 0.75
+
+        [[rarities]]
+        name = "Uncommon"
+        price_lower = 21
+        price_upper = 50
+        likelihood = 0.4
+
+        [[rarities]]
+        name = "Rare"
+        price_lower = 51
+        price_upper = 150
+        likelihood = 0.1
+
+        [[herbs]]
+        name = "Arrow Root"
+        rarity = "Common"
+        biomes = ["Forest"]
+
+        [[herbs]]
+        name = "Blood Grass"
+        rarity = "Common"
+        biomes = ["Desert"]
+
+        [[herbs]]
+        name = "Elven Ivy"
+        rarity = "Common"
+        biomes = ["Forest", "Hills"]
+
+        [[herbs]]
+        name = "Fennel Silk"
+        rarity = "Uncommon"
+        biomes = ["Arctic"]
+
+        [[herbs]]
+        name = "Lavender Sprig"
+        rarity = "Uncommon"
+        biomes = ["Forest"]
+
+        [[herbs]]
+        name = "Mandrake Root"
+        rarity = "Uncommon"
+        biomes = ["Forest", "Swamp"]
+
+        [[herbs]]
+        name = "Nightshade Berries"
+        rarity = "Rare"
+        biomes = ["Forest"]
+
+        [[herbs]]
+        name = "Wild Sageroot"
+        rarity = "Common"
+        biomes = ["Forest", "Grasslands"]
+    "#;
+
+    fn stock(seed: &str) -> Vec<(String, u16, u64)> {
+        let cfg = toml::from_str::<Config>(CONFIG)
+            .unwrap()
+            .resolved()
+            .unwrap();
+        let mut rng = Seed::from(seed.to_string()).rng();
+        generate_stock(&cfg, None, &mut rng)
+            .into_iter()
+            .map(|herb_stock| (herb_stock.herb.name, herb_stock.quantity, herb_stock.price))
+            .collect()
+    }
+
+    /// A seed must keep producing the same market across releases, or campaigns lose the
+    /// markets their players visited.
+    #[test]
+    fn seeds_produce_pinned_stock() {
+        let pinned = |stock: &[(&str, u16, u64)]| -> Vec<(String, u16, u64)> {
+            stock
+                .iter()
+                .map(|&(name, quantity, price)| (name.to_string(), quantity, price))
+                .collect()
+        };
+        assert_eq!(
+            stock("42"),
+            pinned(&[
+                ("Arrow Root", 3, 1600),
+                ("Elven Ivy", 9, 1400),
+                ("Mandrake Root", 1, 3600),
+            ])
+        );
+        assert_eq!(
+            stock("Waterdeep-session-12"),
+            pinned(&[
+                ("Arrow Root", 1, 900),
+                ("Elven Ivy", 2, 1800),
+                ("Mandrake Root", 1, 3000),
+                ("Wild Sageroot", 1, 1200),
+            ])
+        );
+    }
+}